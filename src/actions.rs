@@ -0,0 +1,431 @@
+//! Actions the user can trigger, and the key bindings that trigger them.
+//!
+//! Bindings are written as `Ctrl+Shift+S`, `F11`, `Space` or a single character such as `?`.
+//! Characters are matched against the text the key produces, so they work regardless of the
+//! keyboard layout. The defaults can be overridden per action in the `[keys]` config section.
+
+use std::collections::HashMap;
+
+use glium::glutin::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    ToggleFullscreen,
+    ToggleAlwaysOnTop,
+    ToggleBorderless,
+    CycleGpu,
+    /// Show the graphics card with this index; offered by the context menu.
+    SelectGpu(u32),
+    CycleLayout,
+    SwitchMode,
+    ToggleTimer,
+    ResetTimer,
+    ToggleHardware,
+    ToggleStatistics,
+    ResetStatistics,
+    NextTheme,
+    Screenshot,
+    ToggleHelp,
+    ContextMenu,
+    Quit,
+}
+
+impl Action {
+    /// Every action that can be bound by name; `SelectGpu` is only reachable from the menu.
+    pub const ALL: [Action; 16] = [
+        Action::ToggleFullscreen,
+        Action::ToggleAlwaysOnTop,
+        Action::ToggleBorderless,
+        Action::CycleGpu,
+        Action::CycleLayout,
+        Action::SwitchMode,
+        Action::ToggleTimer,
+        Action::ResetTimer,
+        Action::ToggleHardware,
+        Action::ToggleStatistics,
+        Action::ResetStatistics,
+        Action::NextTheme,
+        Action::Screenshot,
+        Action::ToggleHelp,
+        Action::ContextMenu,
+        Action::Quit,
+    ];
+
+    /// The name used for the action in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::ToggleAlwaysOnTop => "toggle_always_on_top",
+            Action::ToggleBorderless => "toggle_borderless",
+            Action::CycleGpu => "cycle_gpu",
+            Action::SelectGpu(_) => "select_gpu",
+            Action::CycleLayout => "cycle_layout",
+            Action::SwitchMode => "switch_mode",
+            Action::ToggleTimer => "toggle_timer",
+            Action::ResetTimer => "reset_timer",
+            Action::ToggleHardware => "toggle_hardware",
+            Action::ToggleStatistics => "toggle_statistics",
+            Action::ResetStatistics => "reset_statistics",
+            Action::NextTheme => "next_theme",
+            Action::Screenshot => "screenshot",
+            Action::ToggleHelp => "toggle_help",
+            Action::ContextMenu => "context_menu",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|a| a.name() == name)
+    }
+
+    fn default_bindings(self) -> &'static [&'static str] {
+        match self {
+            Action::ToggleFullscreen => &["F", "F11"],
+            Action::ToggleAlwaysOnTop => &["A"],
+            Action::ToggleBorderless => &["B"],
+            Action::CycleGpu => &["G"],
+            Action::SelectGpu(_) => &[],
+            Action::CycleLayout => &["L"],
+            Action::SwitchMode => &["M"],
+            Action::ToggleTimer => &["Space"],
+            Action::ResetTimer => &["R"],
+            Action::ToggleHardware => &["H"],
+            Action::ToggleStatistics => &["S"],
+            Action::ResetStatistics => &["Shift+S"],
+            Action::NextTheme => &["T"],
+            Action::Screenshot => &["F12", "Ctrl+S"],
+            Action::ToggleHelp => &["?", "F1"],
+            Action::ContextMenu => &["Menu"],
+            Action::Quit => &["Escape", "Ctrl+Q"],
+        }
+    }
+}
+
+/// A key together with the modifiers that have to be held.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCombo),
+    Char(char),
+}
+
+impl Binding {
+    pub fn parse(s: &str) -> Option<Binding> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if !c.is_ascii_alphanumeric() {
+                return Some(Binding::Char(c));
+            }
+        }
+
+        let mut combo = KeyCombo {
+            key: VirtualKeyCode::Escape,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            logo: false,
+        };
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop()?;
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "shift" => combo.shift = true,
+                "alt" => combo.alt = true,
+                "logo" | "super" | "cmd" | "win" => combo.logo = true,
+                _ => return None,
+            }
+        }
+        combo.key = key_code(key)?;
+        Some(Binding::Key(combo))
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Char(c) => write!(f, "{}", c),
+            Binding::Key(combo) => {
+                if combo.ctrl {
+                    write!(f, "Ctrl+")?;
+                }
+                if combo.alt {
+                    write!(f, "Alt+")?;
+                }
+                if combo.shift {
+                    write!(f, "Shift+")?;
+                }
+                if combo.logo {
+                    write!(f, "Logo+")?;
+                }
+                write!(f, "{}", key_name(combo.key))
+            }
+        }
+    }
+}
+
+/// Maps key presses to actions.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Binding, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_config(&HashMap::new())
+    }
+}
+
+impl Keymap {
+    /// The default bindings, with the actions named in `config` rebound to the given keys.
+    pub fn from_config(config: &HashMap<String, Vec<String>>) -> Keymap {
+        let mut bindings = HashMap::new();
+        for &action in Action::ALL.iter() {
+            if !config.contains_key(action.name()) {
+                for b in action.default_bindings() {
+                    bindings.insert(Binding::parse(b).unwrap(), action);
+                }
+            }
+        }
+        for (name, keys) in config {
+            let action = match Action::from_name(name) {
+                Some(action) => action,
+                None => {
+                    eprintln!("unknown action in key bindings: {}", name);
+                    continue;
+                }
+            };
+            for key in keys {
+                match Binding::parse(key) {
+                    Some(binding) => {
+                        bindings.insert(binding, action);
+                    }
+                    None => eprintln!("invalid key binding for {}: {}", name, key),
+                }
+            }
+        }
+        Keymap { bindings }
+    }
+
+    /// The action bound to a window event, if any.
+    ///
+    /// Keys trigger on release, like the original fullscreen toggle, so holding a key does not
+    /// repeat the action.
+    pub fn action(&self, event: &Event) -> Option<Action> {
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => self.bindings.get(&Binding::Char(*c)).cloned(),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Released,
+                                virtual_keycode: Some(key),
+                                modifiers,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => self.key_action(*key, *modifiers),
+            _ => None,
+        }
+    }
+
+    pub fn key_action(&self, key: VirtualKeyCode, modifiers: ModifiersState) -> Option<Action> {
+        let combo = KeyCombo {
+            key,
+            ctrl: modifiers.ctrl,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+            logo: modifiers.logo,
+        };
+        self.bindings.get(&Binding::Key(combo)).cloned()
+    }
+
+    /// `(bindings, action)` for every bound action, for the help overlay.
+    pub fn help(&self) -> Vec<(String, Action)> {
+        Action::ALL
+            .iter()
+            .filter_map(|&action| {
+                let mut keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|&(_, &a)| a == action)
+                    .map(|(b, _)| b.to_string())
+                    .collect();
+                if keys.is_empty() {
+                    return None;
+                }
+                keys.sort();
+                Some((keys.join(", "), action))
+            })
+            .collect()
+    }
+}
+
+macro_rules! key_names {
+    ($($name:expr => $key:ident,)*) => {
+        fn key_code(name: &str) -> Option<VirtualKeyCode> {
+            let lower = name.to_ascii_lowercase();
+            $(if lower == $name.to_ascii_lowercase() {
+                return Some(VirtualKeyCode::$key);
+            })*
+            match lower.as_str() {
+                "enter" => Some(VirtualKeyCode::Return),
+                "esc" => Some(VirtualKeyCode::Escape),
+                "backspace" => Some(VirtualKeyCode::Back),
+                "printscreen" => Some(VirtualKeyCode::Snapshot),
+                _ => None,
+            }
+        }
+
+        fn key_name(key: VirtualKeyCode) -> String {
+            match key {
+                $(VirtualKeyCode::$key => $name.to_string(),)*
+                other => format!("{:?}", other),
+            }
+        }
+    };
+}
+
+key_names! {
+    "A" => A, "B" => B, "C" => C, "D" => D, "E" => E, "F" => F, "G" => G, "H" => H, "I" => I,
+    "J" => J, "K" => K, "L" => L, "M" => M, "N" => N, "O" => O, "P" => P, "Q" => Q, "R" => R,
+    "S" => S, "T" => T, "U" => U, "V" => V, "W" => W, "X" => X, "Y" => Y, "Z" => Z,
+    "0" => Key0, "1" => Key1, "2" => Key2, "3" => Key3, "4" => Key4,
+    "5" => Key5, "6" => Key6, "7" => Key7, "8" => Key8, "9" => Key9,
+    "F1" => F1, "F2" => F2, "F3" => F3, "F4" => F4, "F5" => F5, "F6" => F6,
+    "F7" => F7, "F8" => F8, "F9" => F9, "F10" => F10, "F11" => F11, "F12" => F12,
+    "Escape" => Escape, "Space" => Space, "Return" => Return, "Tab" => Tab, "Back" => Back,
+    "Delete" => Delete, "Insert" => Insert, "Home" => Home, "End" => End,
+    "PageUp" => PageUp, "PageDown" => PageDown,
+    "Left" => Left, "Right" => Right, "Up" => Up, "Down" => Down,
+    "Snapshot" => Snapshot, "Menu" => Apps,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(ctrl: bool, shift: bool) -> ModifiersState {
+        ModifiersState {
+            ctrl,
+            shift,
+            ..ModifiersState::default()
+        }
+    }
+
+    fn plain() -> ModifiersState {
+        ModifiersState::default()
+    }
+
+    fn config(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|&(name, keys)| {
+                let keys = keys.iter().map(|k| k.to_string()).collect();
+                (name.to_string(), keys)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_bindings() {
+        let keymap = Keymap::default();
+        let key = |key| keymap.key_action(key, plain());
+        assert_eq!(key(VirtualKeyCode::F), Some(Action::ToggleFullscreen));
+        assert_eq!(key(VirtualKeyCode::F11), Some(Action::ToggleFullscreen));
+        assert_eq!(key(VirtualKeyCode::H), Some(Action::ToggleHardware));
+        assert_eq!(key(VirtualKeyCode::Escape), Some(Action::Quit));
+        assert_eq!(key(VirtualKeyCode::Z), None);
+        let help = Binding::Char('?');
+        assert_eq!(keymap.bindings.get(&help), Some(&Action::ToggleHelp));
+    }
+
+    #[test]
+    fn shift_is_part_of_the_binding() {
+        let keymap = Keymap::default();
+        let s = VirtualKeyCode::S;
+        assert_eq!(keymap.key_action(s, plain()), Some(Action::ToggleStatistics));
+        let shifted = keymap.key_action(s, modifiers(false, true));
+        assert_eq!(shifted, Some(Action::ResetStatistics));
+    }
+
+    #[test]
+    fn ctrl_is_part_of_the_binding() {
+        let keymap = Keymap::default();
+        let s = VirtualKeyCode::S;
+        assert_eq!(keymap.key_action(s, modifiers(true, false)), Some(Action::Screenshot));
+        assert_eq!(keymap.key_action(s, plain()), Some(Action::ToggleStatistics));
+        assert_eq!(keymap.key_action(s, modifiers(true, true)), None);
+    }
+
+    #[test]
+    fn rebinding_replaces_the_defaults() {
+        let keymap = Keymap::from_config(&config(&[("toggle_fullscreen", &["Ctrl+Return"])]));
+        assert_eq!(keymap.key_action(VirtualKeyCode::F, plain()), None);
+        assert_eq!(keymap.key_action(VirtualKeyCode::F11, plain()), None);
+        let enter = keymap.key_action(VirtualKeyCode::Return, modifiers(true, false));
+        assert_eq!(enter, Some(Action::ToggleFullscreen));
+    }
+
+    #[test]
+    fn rebinding_takes_a_key_from_another_action() {
+        let keymap = Keymap::from_config(&config(&[("quit", &["H"])]));
+        assert_eq!(keymap.key_action(VirtualKeyCode::H, plain()), Some(Action::Quit));
+        assert!(keymap.help().iter().all(|&(_, action)| action != Action::ToggleHardware));
+    }
+
+    #[test]
+    fn parse_bindings() {
+        assert_eq!(Binding::parse("?"), Some(Binding::Char('?')));
+        let f11 = KeyCombo {
+            key: VirtualKeyCode::F11,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            logo: false,
+        };
+        assert_eq!(Binding::parse("F11"), Some(Binding::Key(f11)));
+        let ctrl_shift_s = KeyCombo {
+            key: VirtualKeyCode::S,
+            ctrl: true,
+            shift: true,
+            ..f11
+        };
+        assert_eq!(Binding::parse("Ctrl+Shift+S"), Some(Binding::Key(ctrl_shift_s)));
+        assert_eq!(Binding::parse(" ctrl + shift + s "), Some(Binding::Key(ctrl_shift_s)));
+        assert_eq!(Binding::parse("Hyper+S"), None);
+        assert_eq!(Binding::parse("Ctrl+"), None);
+        assert_eq!(Binding::parse("NoSuchKey"), None);
+    }
+
+    #[test]
+    fn help_lists_sorted_keys_in_action_order() {
+        let help = Keymap::default().help();
+        assert_eq!(help[0], ("F, F11".to_string(), Action::ToggleFullscreen));
+        let keys = |action| {
+            help.iter()
+                .find(|&&(_, a)| a == action)
+                .map(|(keys, _)| keys.as_str())
+        };
+        assert_eq!(keys(Action::Screenshot), Some("Ctrl+S, F12"));
+        assert_eq!(keys(Action::ToggleHelp), Some("?, F1"));
+        assert_eq!(keys(Action::Quit), Some("Ctrl+Q, Escape"));
+        let actions: Vec<Action> = help.iter().map(|&(_, action)| action).collect();
+        assert_eq!(actions, Action::ALL.to_vec());
+    }
+}
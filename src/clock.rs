@@ -1,30 +1,39 @@
-//! This example behaves the same as the `all_winit_glium` example while demonstrating how to run
-//! the `conrod` loop on a separate thread.
+//! The `clock` binary: parses the command line, opens the window and runs the `winit` loop, with
+//! the conrod loop from the `conrod_clock` library on a separate thread.
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-#[macro_use]
+extern crate chrono;
+extern crate conrod_clock;
 extern crate conrod_core;
 extern crate conrod_glium;
 extern crate conrod_winit;
-extern crate find_folder;
+extern crate ctrlc;
 extern crate glium;
 extern crate image;
-extern crate nvml_wrapper;
 extern crate winit;
 
+use std::path::PathBuf;
+
 use conrod_glium::Renderer;
 use glium::backend::glutin;
 use glium::Surface;
 
-mod conrod_thread;
-mod gui;
-mod my_widgets;
+use conrod_clock::actions::{Action, Keymap};
+use conrod_clock::config::Config;
+use conrod_clock::conrod_thread::{run_conrod, Message};
+use conrod_clock::error::{self, Error};
+use conrod_clock::gesture::{GestureMap, Recognizer, TouchGesture, TouchMap, TouchRecognizer};
+use conrod_clock::metrics::{self, MetricsSource};
+use conrod_clock::window::{Dragger, WindowState};
 
-use conrod_thread::run_conrod;
+const USAGE: &str = "usage: clock [--config <file>] [--record <file> | --replay <file>]
 
-const WIN_W: u32 = 720;
-const WIN_H: u32 = 1080;
+  --config <file>        read settings from <file> instead of clock.toml, like CLOCK_CONFIG
+  --record <file>        write the graphics card and system samples to <file>
+  --replay <file>        show the samples from a recording instead of this machine's
+  --replay-speed <n>     play the recording <n> times as fast, 1 by default
+  -h, --help             show this help";
 
 pub struct GliumDisplayWinitWrapper(pub glutin::Display);
 
@@ -37,20 +46,52 @@ impl conrod_winit::WinitWindow for GliumDisplayWinitWrapper {
     }
 }
 
+/// Command line options that are not config settings.
+#[derive(Debug, Default)]
+struct Options {
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    replay_speed: Option<f64>,
+}
+
 fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(options) {
+        error::report(&e);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), Error> {
+    let config = Config::load()?;
+    let keymap = Keymap::from_config(&config.keys);
+    let gesture_map = GestureMap::from_config(&config.mouse.actions);
+    let mut gestures = Recognizer::new((&config.mouse).into());
+    let touch_map = TouchMap::from_config(&config.touch.actions);
+    let mut touches = TouchRecognizer::new((&config.touch).into());
+
     // Build the window.
     let mut events_loop = glium::glutin::EventsLoop::new();
+    let mut window_state = WindowState::load();
     let window = glium::glutin::WindowBuilder::new()
         .with_title("Klok in Rust")
-        .with_window_icon(load_icon())
-        .with_dimensions((WIN_W, WIN_H).into());
+        .with_window_icon(load_icon());
+    let window = window_state.builder(window, &config.window, &events_loop);
     let context = glium::glutin::ContextBuilder::new()
         .with_vsync(true)
         .with_multisampling(4);
-    let display = glium::Display::new(window, context, &events_loop).unwrap();
+    let display = glium::Display::new(window, context, &events_loop)?;
     let display = GliumDisplayWinitWrapper(display);
-
-    const POLL_RATE: std::time::Duration = std::time::Duration::from_millis(250);
+    window_state.restore_position(display.0.gl_window().window());
+    let mut borderless = config.window.borderless;
+    let mut always_on_top = config.window.always_on_top;
+    let mut dragger = Dragger::default();
 
     // A type used for converting `conrod_core::render::Primitives` into `Command`s that can be used
     // for drawing to the glium `Surface`.
@@ -61,7 +102,7 @@ fn main() {
     // - a `Vec` for collecting `backend::glium::Vertex`s generated when translating the
     // `conrod_core::render::Primitive`s.
     // - a `Vec` of commands that describe how to draw the vertices.
-    let mut renderer = Renderer::new(&display.0).unwrap();
+    let mut renderer = Renderer::new(&display.0)?;
 
     let image_map = conrod_core::image::Map::new();
 
@@ -69,208 +110,337 @@ fn main() {
     let (event_tx, event_rx) = std::sync::mpsc::channel();
     // A channel to send `render::Primitive`s from the conrod thread to the `winit thread.
     let (render_tx, render_rx) = std::sync::mpsc::channel();
+    // A channel for actions picked in the conrod thread's context menu.
+    let (action_tx, action_rx) = std::sync::mpsc::channel();
     // Clone the handle to the events loop so that we can interrupt it when ready to draw.
     let events_loop_proxy = events_loop.create_proxy();
 
-    // Spawn the conrod loop on its own thread.
-    std::thread::spawn(move || run_conrod(event_rx, render_tx, events_loop_proxy));
-
-    // Run the `winit` loop.
-    // let mut last_update = std::time::Instant::now();
-    let mut closed = false;
-    let mut prev = std::time::Instant::now();
-    let mut last_update = std::time::Instant::now();
-    let mut now;
-    let mut d = DoubleClicker::default();
-
-    while !closed {
-        // send every half second update time event
-        now = std::time::Instant::now();
-        if now.duration_since(prev) > std::time::Duration::from_millis(500) {
-            event_tx.send(conrod_core::event::Input::Redraw).unwrap();
-            prev = now;
+    // Sample the hardware on its own thread, sending the samples straight to the conrod thread.
+    let (sampler_tx, sampler_rx) = std::sync::mpsc::channel();
+    let metrics_tx = event_tx.clone();
+    let interval = std::time::Duration::from_millis(config.metrics.interval_ms);
+    let system = metrics::SystemSource::new(&config.system);
+    let replay = match options.replay {
+        Some(ref path) => Some(metrics::ReplaySource::open(
+            path,
+            options.replay_speed.unwrap_or(1.0),
+        )?),
+        None => None,
+    };
+    let recording = match options.record {
+        Some(ref path) => Some(std::fs::File::create(path).map_err(|e| {
+            Error::io(&format!("could not create recording {}", path.display()), e)
+        })?),
+        None => None,
+    };
+    std::thread::spawn(move || {
+        // Without a supported graphics card the clock still runs, just without the card's panels.
+        let source: Box<dyn MetricsSource> = match replay {
+            Some(replay) => Box::new(replay),
+            None => metrics::detect(),
+        };
+        match recording {
+            Some(file) => {
+                let source = metrics::Recorder::new(source, file);
+                metrics::run_sampler(source, system, interval, sampler_rx, metrics_tx)
+            }
+            None => metrics::run_sampler(source, system, interval, sampler_rx, metrics_tx),
         }
+    });
+
+    // Spawn the conrod loop on its own thread. However it ends, the `winit` loop is woken so it
+    // can notice and close the window.
+    let help = keymap.help();
+    let exit_proxy = events_loop.create_proxy();
+    let conrod_thread = std::thread::Builder::new()
+        .name("conrod".to_string())
+        .spawn(move || {
+            let _wake = WakeOnDrop(exit_proxy);
+            run_conrod(event_rx, render_tx, action_tx, events_loop_proxy, config, help)
+        })
+        .map_err(|e| Error::io("could not start the conrod thread", e))?;
+
+    // Close the window cleanly on Ctrl-C, and SIGTERM on unix.
+    let shutdown = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        let proxy = events_loop.create_proxy();
+        let handler = ctrlc::set_handler(move || {
+            shutdown.store(true, std::sync::atomic::Ordering::SeqCst);
+            let _ = proxy.wakeup();
+        });
+        if let Err(e) = handler {
+            eprintln!("could not install the shutdown handler: {}", e);
+        }
+    }
 
-        // events_loop.run_forever(|event| {
-        events_loop.poll_events(|event| {
-            let do_fullscreen = || {
-                let gl_window = display.0.gl_window();
-                let window = gl_window.window();
-                if window.get_fullscreen().is_none() {
-                    window.set_fullscreen(Some(window.get_current_monitor()));
-                } else {
-                    window.set_fullscreen(None);
-                }
-            };
-
-            match d.update(&event) {
-                Some(ClickEvent::DoubleClick) => do_fullscreen(),
-                Some(ClickEvent::Click) => println!("CLICK"),
-                None => (),
+    // Run the `winit` loop. It blocks until there is an event; the conrod thread wakes it when
+    // there is something new to draw, so there is no polling while idle.
+    let waker = spawn_waker(events_loop.create_proxy())?;
+    let mut wake_at = None;
+    let mut result = Ok(());
+    events_loop.run_forever(|event| {
+        let mut actions = Vec::new();
+        let mut closed = false;
+
+        if borderless {
+            dragger.update(&event, display.0.gl_window().window());
+        }
+        let now = std::time::Instant::now();
+        if let Some(gesture) = gestures.update(&event, now).or_else(|| gestures.poll(now)) {
+            actions.extend(gesture_map.action(gesture));
+        }
+        match touches.update(&event, now).or_else(|| touches.poll(now)) {
+            Some(TouchGesture::Pinch(factor)) => {
+                closed |= event_tx.send(Message::Zoom(factor)).is_err();
             }
+            Some(gesture) => actions.extend(touch_map.action(gesture)),
+            None => (),
+        }
+        actions.extend(keymap.action(&event));
+
+        // A held button or finger becomes a long press without any further events, so make sure
+        // we are woken up when that can happen.
+        let deadline = gestures.deadline().into_iter().chain(touches.deadline()).min();
+        if let Some(deadline) = deadline.filter(|d| Some(*d) != wake_at) {
+            wake_at = Some(deadline);
+            let _ = waker.send(deadline);
+        }
 
-            // Use the `winit` backend feature to convert the winit event to a conrod one.
-            if let Some(event) = convert_event(event.clone(), &display) {
-                event_tx.send(event).unwrap();
-            }
+        // Use the `winit` backend feature to convert the winit event to a conrod one.
+        if let Some(event) = convert_event(event.clone(), &display) {
+            closed |= event_tx.send(Message::Input(event)).is_err();
+        }
 
-            match event {
-                glium::glutin::Event::WindowEvent { event, .. } => match event {
-                    // Break from the loop upon `Escape`.
-                    glium::glutin::WindowEvent::CloseRequested
-                    | glium::glutin::WindowEvent::KeyboardInput {
-                        input:
-                            glium::glutin::KeyboardInput {
-                                virtual_keycode: Some(glium::glutin::VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => {
-                        closed = true;
-                        // return glium::glutin::ControlFlow::Break;
-                        return;
+        match event {
+            glium::glutin::Event::WindowEvent { event, .. } => match event {
+                glium::glutin::WindowEvent::CloseRequested => closed = true,
+                // We must re-draw on `Resized`, as the event loops become blocked during
+                // resize on macOS.
+                glium::glutin::WindowEvent::Resized(..) => {
+                    if let Some(primitives) = render_rx.iter().next() {
+                        result = draw(&display.0, &mut renderer, &image_map, &primitives);
                     }
-                    // We must re-draw on `Resized`, as the event loops become blocked during
-                    // resize on macOS.
-                    glium::glutin::WindowEvent::Resized(..) => {
-                        if let Some(primitives) = render_rx.iter().next() {
-                            draw(&display.0, &mut renderer, &image_map, &primitives);
-                        }
+                }
+                _ => {}
+            },
+            // Draw the most recently received `conrod_core::render::Primitives` sent from the `Ui`.
+            // We are also woken when the conrod thread exits or a shutdown signal arrives.
+            glium::glutin::Event::Awakened => {
+                match latest_primitives(&render_rx) {
+                    Ok(Some(primitives)) => {
+                        result = draw(&display.0, &mut renderer, &image_map, &primitives);
                     }
-                    glium::glutin::WindowEvent::KeyboardInput {
-                        input:
-                            glium::glutin::KeyboardInput {
-                                virtual_keycode: Some(glium::glutin::VirtualKeyCode::F),
-                                state: glium::glutin::ElementState::Released,
-                                ..
-                            },
-                        ..
-                    } => do_fullscreen(),
-                    _ => {}
-                },
-                // glium::glutin::Event::Awakened => return glium::glutin::ControlFlow::Break,
-                glium::glutin::Event::Awakened => return,
-                _ => (),
+                    Ok(None) => (),
+                    Err(()) => closed = true,
+                }
+                closed |= shutdown.load(std::sync::atomic::Ordering::SeqCst);
             }
+            _ => (),
+        }
 
-            // glium::glutin::ControlFlow::Continue
-        });
+        actions.extend(action_rx.try_iter());
 
-        // Draw the most recently received `conrod_core::render::Primitives` sent from the `Ui`.
-        if let Some(primitives) = render_rx.try_iter().last() {
-            draw(&display.0, &mut renderer, &image_map, &primitives);
+        // Window actions are handled here, everything else is up to the conrod thread.
+        for action in actions {
+            match action {
+                Action::ToggleFullscreen => toggle_fullscreen(&display.0),
+                Action::ToggleAlwaysOnTop => {
+                    always_on_top = !always_on_top;
+                    display.0.gl_window().window().set_always_on_top(always_on_top);
+                }
+                Action::ToggleBorderless => {
+                    borderless = !borderless;
+                    display.0.gl_window().window().set_decorations(!borderless);
+                }
+                Action::Screenshot => {
+                    if let Err(e) = screenshot(&display.0) {
+                        eprintln!("could not save screenshot: {}", e);
+                    }
+                }
+                Action::Quit => closed = true,
+                Action::CycleGpu | Action::SelectGpu(_) => {
+                    // The sampler may have given up, in which case there is nothing to switch.
+                    let _ = sampler_tx.send(action);
+                }
+                action => closed |= event_tx.send(Message::Action(action)).is_err(),
+            }
         }
 
-        // let sixteen_ms = std::time::Duration::from_millis(25);
-        let duration_since_last_update = now.duration_since(last_update);
-        if duration_since_last_update < POLL_RATE {
-            std::thread::sleep(POLL_RATE - duration_since_last_update);
+        if closed || result.is_err() {
+            glium::glutin::ControlFlow::Break
+        } else {
+            glium::glutin::ControlFlow::Continue
         }
+    });
+
+    window_state.update(display.0.gl_window().window());
+    window_state.save();
+
+    // Ask the conrod thread to stop and report how it ended. The sampler may be stuck in a driver
+    // call, so it is left to end with the process.
+    let _ = event_tx.send(Message::Quit);
+    let conrod_result = conrod_thread.join().unwrap_or_else(|panic| {
+        Err(Error::Panic {
+            thread: "conrod",
+            message: panic_message(&*panic),
+        })
+    });
+    result.and(conrod_result)
+}
 
-        last_update = std::time::Instant::now();
+/// Apply the command line. The config path goes through `CLOCK_CONFIG`, so the window state file
+/// follows it; the other options are returned.
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let path = args.next().ok_or("--config needs a file")?;
+                std::env::set_var("CLOCK_CONFIG", path);
+            }
+            "--record" => {
+                let path = args.next().ok_or("--record needs a file")?;
+                options.record = Some(PathBuf::from(path));
+            }
+            "--replay" => {
+                let path = args.next().ok_or("--replay needs a file")?;
+                options.replay = Some(PathBuf::from(path));
+            }
+            "--replay-speed" => {
+                let speed = args.next().ok_or("--replay-speed needs a number")?;
+                match speed.parse::<f64>() {
+                    Ok(speed) if speed > 0.0 => options.replay_speed = Some(speed),
+                    _ => return Err(format!("invalid replay speed: {}", speed)),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    if options.record.is_some() && options.replay.is_some() {
+        return Err("--record and --replay cannot be used together".to_string());
     }
+    if options.replay_speed.is_some() && options.replay.is_none() {
+        return Err("--replay-speed needs --replay".to_string());
+    }
+    Ok(options)
 }
 
-conrod_winit::conversion_fns!();
+/// Wakes the `winit` loop when dropped, even when the owning thread panics.
+struct WakeOnDrop(glium::glutin::EventsLoopProxy);
 
-// Draws the given `primitives` to the given `Display`.
-fn draw(
-    display: &glium::Display,
-    renderer: &mut Renderer,
-    image_map: &conrod_core::image::Map<glium::Texture2d>,
-    primitives: &conrod_core::render::OwnedPrimitives,
-) {
-    renderer.fill(display, primitives.walk(), &image_map);
-    let mut target = display.draw();
-    target.clear_color(0.0, 0.0, 0.0, 1.0);
-    renderer.draw(display, &mut target, &image_map).unwrap();
-    target.finish().unwrap();
+impl Drop for WakeOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.wakeup();
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct DoubleClicker {
-    between_pressed_released: std::time::Duration,
-    between_clicks: std::time::Duration,
-    prev_click: std::time::Instant,
-    prev_pressed: std::time::Instant,
-    prev_released: std::time::Instant,
+/// Starts a thread that wakes the `winit` loop at the last deadline sent to it. It ends when the
+/// sender is dropped.
+fn spawn_waker(
+    proxy: glium::glutin::EventsLoopProxy,
+) -> Result<std::sync::mpsc::Sender<std::time::Instant>, Error> {
+    use std::sync::mpsc::RecvTimeoutError;
+
+    let (deadline_tx, deadline_rx) = std::sync::mpsc::channel::<std::time::Instant>();
+    std::thread::Builder::new()
+        .name("waker".to_string())
+        .spawn(move || {
+            let mut deadline = None;
+            loop {
+                let received = match deadline {
+                    Some(at) => {
+                        let now = std::time::Instant::now();
+                        deadline_rx.recv_timeout(at.saturating_duration_since(now))
+                    }
+                    None => deadline_rx
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    // A newer deadline replaces the one being waited for.
+                    Ok(at) => deadline = Some(at),
+                    Err(RecvTimeoutError::Timeout) => {
+                        let _ = proxy.wakeup();
+                        deadline = None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        })
+        .map_err(|e| Error::io("could not start the waker thread", e))?;
+    Ok(deadline_tx)
 }
 
-impl Default for DoubleClicker {
-    fn default() -> Self {
-        DoubleClicker {
-            between_pressed_released: std::time::Duration::from_millis(115),
-            between_clicks: std::time::Duration::from_millis(500),
-            prev_click: std::time::Instant::now(),
-            prev_pressed: std::time::Instant::now()
-                .checked_sub(std::time::Duration::from_millis(50))
-                .unwrap(),
-            prev_released: std::time::Instant::now(),
+/// All pending primitives are outdated except the last; `Err` once the conrod thread is gone.
+fn latest_primitives(
+    render_rx: &std::sync::mpsc::Receiver<conrod_core::render::OwnedPrimitives>,
+) -> Result<Option<conrod_core::render::OwnedPrimitives>, ()> {
+    let mut latest = None;
+    loop {
+        match render_rx.try_recv() {
+            Ok(primitives) => latest = Some(primitives),
+            Err(std::sync::mpsc::TryRecvError::Empty) => return Ok(latest),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => return Err(()),
         }
     }
 }
 
-enum ClickEvent {
-    DoubleClick,
-    Click,
-}
-
-impl DoubleClicker {
-    pub fn update(&mut self, event: &glium::glutin::Event) -> Option<ClickEvent> {
-        if self.handle_event(event).is_some() {
-            if self.is_click() {
-                let event = match self.is_double_click() {
-                    true => ClickEvent::DoubleClick,
-                    false => ClickEvent::Click,
-                };
-                self.prev_click = std::time::Instant::now();
-                return Some(event);
-            };
-        };
-        None
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
     }
+}
 
-    pub fn handle_event(&mut self, event: &glium::glutin::Event) -> Option<()> {
-        match &event {
-            glium::glutin::Event::WindowEvent {
-                event: window_event,
-                ..
-            } => match window_event {
-                glium::glutin::WindowEvent::MouseInput {
-                    state: mouse_state,
-                    button,
-                    ..
-                } => match (button, mouse_state) {
-                    (glium::glutin::MouseButton::Left, glium::glutin::ElementState::Pressed) => {
-                        self.prev_pressed = std::time::Instant::now();
-                        return Some(());
-                    }
-                    (glium::glutin::MouseButton::Left, glium::glutin::ElementState::Released) => {
-                        self.prev_released = std::time::Instant::now();
-                        return Some(());
-                    }
-                    (_, _) => (),
-                },
-                _ => (),
-            },
-            _ => (),
-        };
-        None
-    }
+conrod_winit::conversion_fns!();
 
-    fn is_click(&self) -> bool {
-        if self.prev_pressed < self.prev_released {
-            self.prev_pressed + self.between_pressed_released > self.prev_released
-        } else {
-            false
-        }
+fn toggle_fullscreen(display: &glium::Display) {
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    if window.get_fullscreen().is_none() {
+        window.set_fullscreen(Some(window.get_current_monitor()));
+    } else {
+        window.set_fullscreen(None);
     }
+}
 
-    fn is_double_click(&self) -> bool {
-        self.prev_click + self.between_clicks > std::time::Instant::now()
-    }
+/// Save what is currently on screen as `clock-<date>-<time>.png` in the working directory.
+fn screenshot(display: &glium::Display) -> Result<(), String> {
+    let raw: glium::texture::RawImage2d<u8> =
+        display.read_front_buffer().map_err(|e| format!("{:?}", e))?;
+    let buffer = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+        .ok_or_else(|| "unexpected front buffer size".to_string())?;
+    // OpenGL rows start at the bottom.
+    let image = image::DynamicImage::ImageRgba8(buffer).flipv();
+    let path = format!("clock-{}.png", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+    image.save(&path).map_err(|e| e.to_string())
 }
 
+// Draws the given `primitives` to the given `Display`.
+fn draw(
+    display: &glium::Display,
+    renderer: &mut Renderer,
+    image_map: &conrod_core::image::Map<glium::Texture2d>,
+    primitives: &conrod_core::render::OwnedPrimitives,
+) -> Result<(), Error> {
+    renderer.fill(display, primitives.walk(), &image_map);
+    let mut target = display.draw();
+    target.clear_color(0.0, 0.0, 0.0, 1.0);
+    let drawn = renderer.draw(display, &mut target, &image_map);
+    // The frame has to be finished even when drawing failed, or glium panics when it is dropped.
+    let finished = target.finish();
+    drawn?;
+    finished?;
+    Ok(())
+}
 
 fn load_icon() -> Option<glium::glutin::Icon>{
     let image_data = include_bytes!("../assets/clock.ico");
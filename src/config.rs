@@ -0,0 +1,281 @@
+//! User configuration, read from a TOML file at startup.
+//!
+//! The file is looked up at `$CLOCK_CONFIG` or `clock.toml` in the working directory. Every
+//! section is optional; anything left out falls back to the built in defaults.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use error::Error;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Language of the labels, like `"nl"`; taken from `LANG` when not set.
+    pub language: Option<String>,
+    pub night: NightConfig,
+    pub fonts: FontConfig,
+    pub timer: TimerConfig,
+    pub mouse: MouseConfig,
+    pub touch: TouchConfig,
+    pub window: WindowConfig,
+    pub metrics: MetricsConfig,
+    pub units: UnitsConfig,
+    pub system: SystemConfig,
+    pub statistics: StatisticsConfig,
+    /// Key bindings per action name, replacing that action's default keys.
+    pub keys: HashMap<String, Vec<String>>,
+    /// Screen layouts to cycle through, replacing the built in ones.
+    pub layouts: Vec<LayoutConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimerConfig {
+    /// Length of the countdown timer.
+    pub minutes: u32,
+}
+
+impl Default for TimerConfig {
+    fn default() -> Self {
+        TimerConfig { minutes: 5 }
+    }
+}
+
+/// Font files or system font names, tried in order for each character set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FontConfig {
+    /// Fonts for the big clock digits.
+    pub clock: Vec<String>,
+    /// Fonts for the metric lines.
+    pub metrics: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Time between two hardware samples.
+    pub interval_ms: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig { interval_ms: 1000 }
+    }
+}
+
+/// Which network interfaces, disks and filesystems the system panels show. An empty list shows
+/// every interface but `lo`, every whole disk and only the root filesystem.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SystemConfig {
+    /// Interface names, like `"eth0"`.
+    pub interfaces: Vec<String>,
+    /// Disk names as in `/proc/diskstats`, like `"sda"` or `"nvme0n1"`.
+    pub disks: Vec<String>,
+    /// Mount points, like `"/home"`.
+    pub filesystems: Vec<String>,
+}
+
+/// Lowest, highest, mean and 95th percentile shown under each metric.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StatisticsConfig {
+    /// Show the statistics at startup; they can be toggled with `toggle_statistics`.
+    pub show: bool,
+    /// Only cover the last this many minutes, or everything since startup when 0.
+    pub minutes: u32,
+}
+
+/// How measured values are shown.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UnitsConfig {
+    /// `"binary"` for KiB, MiB and GiB, or `"si"` for kB, MB and GB.
+    pub bytes: String,
+    /// `"celsius"` or `"fahrenheit"`.
+    pub temperature: String,
+    /// Decimals of scaled values, like the one in `7.8 GiB`.
+    pub precision: usize,
+}
+
+impl Default for UnitsConfig {
+    fn default() -> Self {
+        UnitsConfig {
+            bytes: "binary".to_string(),
+            temperature: "celsius".to_string(),
+            precision: 1,
+        }
+    }
+}
+
+/// How the window is decorated at startup.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// No title bar or borders; the window is moved by dragging the clock.
+    pub borderless: bool,
+    pub always_on_top: bool,
+}
+
+/// Mouse gesture timing, and the actions bound to gestures.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MouseConfig {
+    /// Longest press that still counts as a click.
+    pub click_ms: u64,
+    /// Longest pause between the clicks of a double or triple click.
+    pub multi_click_ms: u64,
+    /// How long to hold a button for a long press.
+    pub long_press_ms: u64,
+    /// Action name per gesture, like `right_click = "next_theme"`; `"none"` unbinds a gesture.
+    pub actions: HashMap<String, String>,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        MouseConfig {
+            click_ms: 115,
+            multi_click_ms: 500,
+            long_press_ms: 700,
+            actions: HashMap::new(),
+        }
+    }
+}
+
+/// Touch gesture timing and distances, and the actions bound to touch gestures.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TouchConfig {
+    pub tap_ms: u64,
+    pub double_tap_ms: u64,
+    pub long_press_ms: u64,
+    /// How far a finger may wander during a tap, in logical pixels.
+    pub slop: f64,
+    /// How far a finger has to travel for a swipe.
+    pub swipe_distance: f64,
+    /// Action name per gesture, like `swipe_left = "cycle_layout"`; `"none"` unbinds a gesture.
+    pub actions: HashMap<String, String>,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        TouchConfig {
+            tap_ms: 250,
+            double_tap_ms: 400,
+            long_press_ms: 700,
+            slop: 12.0,
+            swipe_distance: 80.0,
+            actions: HashMap::new(),
+        }
+    }
+}
+
+/// A named arrangement of panels.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LayoutConfig {
+    pub name: String,
+    pub root: LayoutNode,
+}
+
+/// One node of a layout tree, told apart by its keys.
+///
+/// `size` is the share of the parent row or column the node takes, relative to its siblings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LayoutNode {
+    /// `{ panel = "clock", size = 1, scale = 1.0 }`, where `scale` enlarges the text.
+    Panel {
+        panel: String,
+        #[serde(default = "one")]
+        size: f64,
+        #[serde(default = "one")]
+        scale: f64,
+    },
+    /// `{ rows = [...] }`, children stacked from top to bottom.
+    Rows {
+        rows: Vec<LayoutNode>,
+        #[serde(default = "one")]
+        size: f64,
+    },
+    /// `{ grid = ["a", "b", ...], columns = 2 }`, panels filled in row by row.
+    Grid {
+        grid: Vec<String>,
+        columns: usize,
+        #[serde(default = "one")]
+        size: f64,
+    },
+    /// `{ columns = [...] }`, children side by side from left to right.
+    Columns {
+        columns: Vec<LayoutNode>,
+        #[serde(default = "one")]
+        size: f64,
+    },
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+/// When and how the clock switches to its dim night theme.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NightConfig {
+    pub enabled: bool,
+    /// Fixed start of the night as `HH:MM`, used when no location is given.
+    pub start: String,
+    /// Fixed end of the night as `HH:MM`.
+    pub end: String,
+    /// Location used to follow sunset and sunrise instead of the fixed times.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// How long the fade between day and night takes.
+    pub transition_minutes: u32,
+    /// Text brightness at full night, between 0 and 1.
+    pub brightness: f32,
+    /// Hide the hardware panel while it is night.
+    pub hide_hardware: bool,
+}
+
+impl Default for NightConfig {
+    fn default() -> Self {
+        NightConfig {
+            enabled: false,
+            start: "22:30".to_string(),
+            end: "07:00".to_string(),
+            latitude: None,
+            longitude: None,
+            transition_minutes: 30,
+            brightness: 0.35,
+            hide_hardware: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        std::env::var_os("CLOCK_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("clock.toml"))
+    }
+
+    /// Load the configuration, using the defaults when there is no config file.
+    pub fn load() -> Result<Config, Error> {
+        let path = Config::path();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => {
+                let context = format!("could not read config {}", path.display());
+                return Err(Error::io(&context, e));
+            }
+        };
+        toml::from_str(&text).map_err(|e| Error::Config {
+            path,
+            message: e.to_string(),
+        })
+    }
+}
@@ -1,36 +1,52 @@
 use {WIN_H, WIN_W};
+use actions::Action;
+use config::Config;
+use error::Error;
+use fonts;
 use gui;
+use metrics::Snapshot;
+
+/// What the main thread sends to the conrod thread.
+pub enum Message {
+    Input(conrod_core::event::Input),
+    Action(Action),
+    /// Scale the text by this factor, from a pinch gesture.
+    Zoom(f64),
+    /// Names of the graphics cards, sent once by the sampler.
+    Devices(Vec<String>),
+    Metrics(Snapshot),
+    /// The window is closing.
+    Quit,
+}
 
 // A function that runs the conrod loop.
 pub fn run_conrod(
-    event_rx: std::sync::mpsc::Receiver<conrod_core::event::Input>,
+    event_rx: std::sync::mpsc::Receiver<Message>,
     render_tx: std::sync::mpsc::Sender<conrod_core::render::OwnedPrimitives>,
+    action_tx: std::sync::mpsc::Sender<Action>,
     events_loop_proxy: glium::glutin::EventsLoopProxy,
-) {
+    config: Config,
+    help: Vec<(String, Action)>,
+) -> Result<(), Error> {
     // Construct our `Ui`.
     let mut ui = conrod_core::UiBuilder::new([WIN_W as f64, WIN_H as f64])
         .theme(gui::theme())
         .build();
 
-    // Add a `Font` to the `Ui`'s `font::Map` from file.
-    // let assets = find_folder::Search::KidsThenParents(3, 5).for_folder("assets").unwrap();
-    // let font_path = assets.join("NotoSans-Regular.ttf");
-    // ui.fonts.insert_from_file(font_path).unwrap();
-    let font_data: &[u8] = include_bytes!("../assets/NotoSans-Regular.ttf");
-    let font = conrod_core::text::Font::from_bytes(font_data).unwrap();
-    ui.fonts.insert(font);
+    // Add the configured fonts, and the embedded fallback, to the `Ui`'s `font::Map`.
+    let fonts = fonts::load(&config.fonts, &mut ui.fonts)?;
 
     // A demonstration of some app state that we want to control with the conrod GUI.
-    let mut app = gui::Clock::new();
-    let nvidia = nvml_wrapper::NVML::init().unwrap();
-    let graphics_card = nvidia.device_by_index(0).unwrap();
+    let mut app = gui::App::new(&config, fonts, help);
 
     // The `widget::Id` of each widget instantiated in `gui::gui`.
-    let ids = gui::Ids::new(ui.widget_id_generator());
+    let mut ids = gui::Ids::new(ui.widget_id_generator());
 
     // Many widgets require another frame to finish drawing after clicks or hovers, so we
     // insert an update into the conrod loop using this `bool` after each event.
     let mut needs_update = true;
+    // When the display next changes by itself, like the seconds ticking over.
+    let mut next_tick = std::time::Instant::now();
     'conrod: loop {
         // Collect any pending events.
         let mut events = Vec::new();
@@ -39,25 +55,53 @@ pub fn run_conrod(
             events.push(event);
         }
 
-        // If there are no events pending, wait for them.
+        // If there are no events pending, wait for them or for the next tick.
         if events.is_empty() || !needs_update {
-            match event_rx.recv() {
-                Ok(event) => events.push(event),
-                Err(_) => break 'conrod,
-            };
+            let now = std::time::Instant::now();
+            if next_tick > now {
+                match event_rx.recv_timeout(next_tick - now) {
+                    Ok(event) => events.push(event),
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => (),
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break 'conrod,
+                };
+            }
         }
 
-        needs_update = false;
+        needs_update = std::time::Instant::now() >= next_tick;
 
-        // Input each event into the `Ui`.
+        // Input each event into the `Ui`, or apply it to the app.
         for event in events {
-            ui.handle_event(event);
+            match event {
+                Message::Input(input) => ui.handle_event(input),
+                Message::Action(Action::ContextMenu) => {
+                    app.menu = match app.menu {
+                        Some(_) => None,
+                        None => Some(ui.global_input().current.mouse.xy),
+                    };
+                }
+                Message::Action(action) => app.handle(action),
+                Message::Zoom(factor) => app.zoom(factor),
+                Message::Devices(names) => app.gpus = names,
+                Message::Metrics(snapshot) => {
+                    app.gpu = snapshot.gpu_index;
+                    app.history.push(&snapshot);
+                    app.metrics = snapshot;
+                }
+                Message::Quit => break 'conrod,
+            }
             needs_update = true;
         }
 
         // Instantiate a GUI demonstrating every widget type provided by conrod.
         if needs_update{
-            gui::gui(&mut ui.set_widgets(), &ids, &mut app, &graphics_card);
+            let picked = gui::gui(&mut ui.set_widgets(), &mut ids, &mut app);
+            next_tick = std::time::Instant::now() + app.until_next_tick();
+            // Menu picks go through the main thread, which owns the window.
+            for action in picked {
+                if action_tx.send(action).is_err() || events_loop_proxy.wakeup().is_err() {
+                    break 'conrod;
+                }
+            }
         }
 
         // Render the `Ui` to a list of primitives that we can send to the main thread for
@@ -67,4 +111,7 @@ pub fn run_conrod(
                 break 'conrod;
             }
         }
-    }}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Errors that stop the clock, and how they are shown to the user.
+//!
+//! Problems the clock can live with, like a missing font or an unwritable window state file, are
+//! only printed. Everything that ends up here is fatal and reported once from `main`.
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The window or its OpenGL context could not be set up.
+    Display(String),
+    /// Drawing or presenting a frame failed.
+    Render(String),
+    Font(String),
+    /// The hardware metrics backend is unavailable.
+    Metrics(String),
+    Config { path: PathBuf, message: String },
+    Io { context: String, error: std::io::Error },
+    /// A worker thread panicked.
+    Panic { thread: &'static str, message: String },
+}
+
+impl Error {
+    pub fn io(context: &str, error: std::io::Error) -> Error {
+        Error::Io {
+            context: context.to_string(),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Display(e) => write!(f, "could not open the window: {}", e),
+            Error::Render(e) => write!(f, "could not draw the window: {}", e),
+            Error::Font(e) => write!(f, "could not load fonts: {}", e),
+            Error::Metrics(e) => write!(f, "hardware metrics are unavailable: {}", e),
+            Error::Config { path, message } => {
+                write!(f, "invalid config {}: {}", path.display(), message)
+            }
+            Error::Io { context, error } => write!(f, "{}: {}", context, error),
+            Error::Panic { thread, message } => {
+                write!(f, "the {} thread panicked: {}", thread, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<glium::backend::glutin::DisplayCreationError> for Error {
+    fn from(e: glium::backend::glutin::DisplayCreationError) -> Error {
+        Error::Display(e.to_string())
+    }
+}
+
+impl From<conrod_glium::RendererCreationError> for Error {
+    fn from(e: conrod_glium::RendererCreationError) -> Error {
+        Error::Display(e.to_string())
+    }
+}
+
+impl From<glium::DrawError> for Error {
+    fn from(e: glium::DrawError) -> Error {
+        Error::Render(e.to_string())
+    }
+}
+
+impl From<glium::SwapBuffersError> for Error {
+    fn from(e: glium::SwapBuffersError) -> Error {
+        Error::Render(format!("{:?}", e))
+    }
+}
+
+/// Tell the user why the clock stopped.
+///
+/// Release builds on Windows have no console, so the message is shown in a message box there.
+pub fn report(error: &Error) {
+    eprintln!("{}", error);
+    #[cfg(all(windows, not(debug_assertions)))]
+    message_box(&error.to_string());
+}
+
+#[cfg(all(windows, not(debug_assertions)))]
+fn message_box(text: &str) {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::winuser::{MessageBoxW, MB_ICONERROR, MB_OK};
+
+    let wide = |s: &str| -> Vec<u16> {
+        std::ffi::OsStr::new(s)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    };
+    let text = wide(text);
+    let caption = wide("Klok in Rust");
+    unsafe {
+        MessageBoxW(
+            std::ptr::null_mut(),
+            text.as_ptr(),
+            caption.as_ptr(),
+            MB_OK | MB_ICONERROR,
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Loading user configured fonts, with a fallback chain for glyphs a font does not have.
+//!
+//! A font is configured either as a path to a font file or as a bare file name, which is then
+//! looked up in the usual system font directories. The embedded Noto Sans is always appended as
+//! the last fallback.
+
+use std::path::{Path, PathBuf};
+
+use conrod_core::text::{font, Font};
+
+use config::FontConfig;
+use error::Error;
+
+const EMBEDDED: &[u8] = include_bytes!("../assets/NotoSans-Regular.ttf");
+
+/// Fonts to try in order; each character is drawn with the first font that has it.
+#[derive(Debug, Clone)]
+pub struct FontChain(Vec<font::Id>);
+
+/// The font chains for the big clock digits and the metric lines.
+#[derive(Debug, Clone)]
+pub struct Fonts {
+    pub clock: FontChain,
+    pub metrics: FontChain,
+}
+
+impl FontChain {
+    /// The first font in the chain with a glyph for `c`, or the first font when none has one.
+    pub fn pick_char(&self, map: &font::Map, c: char) -> font::Id {
+        self.0
+            .iter()
+            .cloned()
+            .find(|&id| map.get(id).map_or(false, |font| font.glyph(c).id().0 != 0))
+            .unwrap_or(self.0[0])
+    }
+
+    /// The first font in the chain that has a glyph for every character of `text`, for text
+    /// that has to be drawn in a single font.
+    ///
+    /// When no font covers everything the font missing the fewest glyphs is used.
+    pub fn pick(&self, map: &font::Map, text: &str) -> font::Id {
+        let mut best = (usize::max_value(), self.0[0]);
+        for &id in &self.0 {
+            let missing = match map.get(id) {
+                Some(font) => missing_glyphs(font, text),
+                None => continue,
+            };
+            if missing == 0 {
+                return id;
+            }
+            if missing < best.0 {
+                best = (missing, id);
+            }
+        }
+        best.1
+    }
+}
+
+fn missing_glyphs(font: &Font, text: &str) -> usize {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .filter(|&c| font.glyph(c).id().0 == 0)
+        .count()
+}
+
+/// Load the configured fonts into `map`.
+///
+/// Fonts that cannot be found or read are reported and skipped; only a broken embedded font is an
+/// error.
+pub fn load(config: &FontConfig, map: &mut font::Map) -> Result<Fonts, Error> {
+    let embedded = Font::from_bytes(EMBEDDED)
+        .map_err(|e| Error::Font(format!("the embedded font is broken: {}", e)))?;
+    let embedded = map.insert(embedded);
+    Ok(Fonts {
+        clock: load_chain(&config.clock, map, embedded),
+        metrics: load_chain(&config.metrics, map, embedded),
+    })
+}
+
+fn load_chain(names: &[String], map: &mut font::Map, embedded: font::Id) -> FontChain {
+    let mut ids: Vec<font::Id> = names
+        .iter()
+        .filter_map(|name| match find_font(name) {
+            Some(path) => match map.insert_from_file(&path) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    eprintln!("could not load font {}: {}", path.display(), e);
+                    None
+                }
+            },
+            None => {
+                eprintln!("font not found: {}", name);
+                None
+            }
+        })
+        .collect();
+    ids.push(embedded);
+    FontChain(ids)
+}
+
+/// Resolve a configured font to a file, searching the system font directories for bare names.
+pub fn find_font(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    if path.components().count() > 1 {
+        return None;
+    }
+    font_dirs()
+        .iter()
+        .filter_map(|dir| search_dir(dir, name, 4))
+        .next()
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        PathBuf::from("/Library/Fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+    ];
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+    if let Some(windir) = std::env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windir).join("Fonts"));
+    }
+    dirs
+}
+
+/// Look for `name`, with or without a font extension, below `dir`.
+fn search_dir(dir: &Path, name: &str, depth: u32) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    let mut subdirs = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_dir() {
+            subdirs.push(path);
+        } else if matches_name(&path, name) {
+            return Some(path);
+        }
+    }
+    if depth == 0 {
+        return None;
+    }
+    subdirs
+        .iter()
+        .filter_map(|sub| search_dir(sub, name, depth - 1))
+        .next()
+}
+
+fn matches_name(path: &Path, name: &str) -> bool {
+    let is_font = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("ttf") || ext.eq_ignore_ascii_case("otf"),
+        None => false,
+    };
+    if !is_font {
+        return false;
+    }
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let stem = path.file_stem().and_then(|n| n.to_str()).unwrap_or("");
+    file_name.eq_ignore_ascii_case(name) || stem.eq_ignore_ascii_case(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font with glyphs for the ten digits only.
+    const DIGITS: &[u8] = include_bytes!("../assets/tests/digits.ttf");
+
+    /// A chain of the digits font with the embedded font as its fallback.
+    fn chain(map: &mut font::Map) -> (FontChain, font::Id, font::Id) {
+        let digits = map.insert(Font::from_bytes(DIGITS).unwrap());
+        let embedded = map.insert(Font::from_bytes(EMBEDDED).unwrap());
+        (FontChain(vec![digits, embedded]), digits, embedded)
+    }
+
+    #[test]
+    fn pick_char_falls_back_per_character() {
+        let mut map = font::Map::new();
+        let (chain, digits, embedded) = chain(&mut map);
+        assert_eq!(chain.pick_char(&map, '5'), digits);
+        assert_eq!(chain.pick_char(&map, 'x'), embedded);
+        assert_eq!(chain.pick_char(&map, ':'), embedded);
+        // Neither font has CJK characters.
+        assert_eq!(chain.pick_char(&map, '字'), digits);
+    }
+
+    #[test]
+    fn pick_covers_the_whole_text() {
+        let mut map = font::Map::new();
+        let (chain, digits, embedded) = chain(&mut map);
+        assert_eq!(chain.pick(&map, "1234"), digits);
+        assert_eq!(chain.pick(&map, "12:34"), embedded);
+        assert_eq!(chain.pick(&map, "字"), digits);
+    }
+}
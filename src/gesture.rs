@@ -0,0 +1,799 @@
+//! Recognizes clicks, double and triple clicks and long presses from mouse button events, and taps,
+//! swipes, long presses and pinches from touch events.
+//!
+//! The recognizers never read the time themselves; every call gets the current `Instant`, so they
+//! can be driven by recorded or synthetic event sequences. Every click is reported as soon as the
+//! button is released, so a double click is preceded by a single click.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use glium::glutin::{ElementState, Event, MouseButton, TouchPhase, WindowEvent};
+
+use actions::Action;
+use config::{MouseConfig, TouchConfig};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GestureKind {
+    Click,
+    DoubleClick,
+    TripleClick,
+    LongPress,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Gesture {
+    pub button: MouseButton,
+    pub kind: GestureKind,
+}
+
+impl Gesture {
+    /// Parse names like `left_click`, `right_long_press` or `middle_double_click`.
+    pub fn parse(name: &str) -> Option<Gesture> {
+        let split = name.find('_')?;
+        let button = match &name[..split] {
+            "left" => MouseButton::Left,
+            "right" => MouseButton::Right,
+            "middle" => MouseButton::Middle,
+            _ => return None,
+        };
+        let kind = match &name[split + 1..] {
+            "click" => GestureKind::Click,
+            "double_click" => GestureKind::DoubleClick,
+            "triple_click" => GestureKind::TripleClick,
+            "long_press" => GestureKind::LongPress,
+            _ => return None,
+        };
+        Some(Gesture { button, kind })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Thresholds {
+    /// Longest time between press and release that still counts as a click.
+    pub click: Duration,
+    /// Longest time between two clicks of a double or triple click.
+    pub multi_click: Duration,
+    /// How long a button has to be held for a long press.
+    pub long_press: Duration,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            click: Duration::from_millis(115),
+            multi_click: Duration::from_millis(500),
+            long_press: Duration::from_millis(700),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct ButtonState {
+    pressed: Option<Instant>,
+    long_press_sent: bool,
+    last_click: Option<Instant>,
+    clicks: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Recognizer {
+    pub thresholds: Thresholds,
+    buttons: HashMap<MouseButton, ButtonState>,
+}
+
+impl Recognizer {
+    pub fn new(thresholds: Thresholds) -> Recognizer {
+        Recognizer {
+            thresholds,
+            buttons: HashMap::new(),
+        }
+    }
+
+    /// Feed a window event, returning the gesture it completes.
+    pub fn update(&mut self, event: &Event, now: Instant) -> Option<Gesture> {
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.press(*button, now);
+                    None
+                }
+                ElementState::Released => self.release(*button, now),
+            },
+            _ => None,
+        }
+    }
+
+    pub fn press(&mut self, button: MouseButton, now: Instant) {
+        let state = self.buttons.entry(button).or_default();
+        state.pressed = Some(now);
+        state.long_press_sent = false;
+    }
+
+    pub fn release(&mut self, button: MouseButton, now: Instant) -> Option<Gesture> {
+        let thresholds = self.thresholds;
+        let state = self.buttons.entry(button).or_default();
+        let pressed = state.pressed.take()?;
+        if state.long_press_sent {
+            return None;
+        }
+        if now.duration_since(pressed) > thresholds.click {
+            state.clicks = 0;
+            return None;
+        }
+
+        state.clicks = match state.last_click {
+            Some(last) if now.duration_since(last) <= thresholds.multi_click => state.clicks + 1,
+            _ => 1,
+        };
+        state.last_click = Some(now);
+        let kind = match state.clicks {
+            1 => GestureKind::Click,
+            2 => GestureKind::DoubleClick,
+            _ => {
+                state.clicks = 0;
+                GestureKind::TripleClick
+            }
+        };
+        Some(Gesture { button, kind })
+    }
+
+    /// When the next long press will be due, if a button is held.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.buttons
+            .values()
+            .filter(|state| !state.long_press_sent)
+            .filter_map(|state| state.pressed)
+            .min()
+            .map(|pressed| pressed + self.thresholds.long_press)
+    }
+
+    /// Report a long press once a button has been held long enough; call this when the
+    /// `deadline` has passed.
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        let long_press = self.thresholds.long_press;
+        for (&button, state) in self.buttons.iter_mut() {
+            let pressed = match state.pressed {
+                Some(pressed) => pressed,
+                None => continue,
+            };
+            if !state.long_press_sent && now.duration_since(pressed) >= long_press {
+                state.long_press_sent = true;
+                state.clicks = 0;
+                return Some(Gesture {
+                    button,
+                    kind: GestureKind::LongPress,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Maps gestures to actions.
+#[derive(Debug, Clone)]
+pub struct GestureMap(HashMap<Gesture, Action>);
+
+impl GestureMap {
+    /// Double click for fullscreen and right click for the menu, with the gestures named in
+    /// `config` added or replaced.
+    pub fn from_config(config: &HashMap<String, String>) -> GestureMap {
+        let mut map = HashMap::new();
+        map.insert(
+            Gesture {
+                button: MouseButton::Left,
+                kind: GestureKind::DoubleClick,
+            },
+            Action::ToggleFullscreen,
+        );
+        map.insert(
+            Gesture {
+                button: MouseButton::Right,
+                kind: GestureKind::Click,
+            },
+            Action::ContextMenu,
+        );
+        for (name, action_name) in config {
+            let gesture = match Gesture::parse(name) {
+                Some(gesture) => gesture,
+                None => {
+                    eprintln!("unknown gesture: {}", name);
+                    continue;
+                }
+            };
+            if action_name == "none" {
+                map.remove(&gesture);
+                continue;
+            }
+            match Action::from_name(action_name) {
+                Some(action) => {
+                    map.insert(gesture, action);
+                }
+                None => eprintln!("unknown action for {}: {}", name, action_name),
+            }
+        }
+        GestureMap(map)
+    }
+
+    pub fn action(&self, gesture: Gesture) -> Option<Action> {
+        self.0.get(&gesture).cloned()
+    }
+}
+
+impl<'a> From<&'a MouseConfig> for Thresholds {
+    fn from(config: &MouseConfig) -> Thresholds {
+        Thresholds {
+            click: Duration::from_millis(config.click_ms),
+            multi_click: Duration::from_millis(config.multi_click_ms),
+            long_press: Duration::from_millis(config.long_press_ms),
+        }
+    }
+}
+
+/// A gesture made on a touchscreen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TouchGesture {
+    Tap,
+    DoubleTap,
+    LongPress,
+    SwipeLeft,
+    SwipeRight,
+    SwipeUp,
+    SwipeDown,
+    /// Two fingers moved apart or together; the distance changed by this factor since the last
+    /// pinch gesture.
+    Pinch(f64),
+}
+
+impl TouchGesture {
+    /// Parse the names used in the config: `tap`, `double_tap`, `long_press` and `swipe_left` etc.
+    pub fn parse(name: &str) -> Option<TouchGesture> {
+        match name {
+            "tap" => Some(TouchGesture::Tap),
+            "double_tap" => Some(TouchGesture::DoubleTap),
+            "long_press" => Some(TouchGesture::LongPress),
+            "swipe_left" => Some(TouchGesture::SwipeLeft),
+            "swipe_right" => Some(TouchGesture::SwipeRight),
+            "swipe_up" => Some(TouchGesture::SwipeUp),
+            "swipe_down" => Some(TouchGesture::SwipeDown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TouchThresholds {
+    /// Longest touch that still counts as a tap.
+    pub tap: Duration,
+    /// Longest pause between the taps of a double tap.
+    pub double_tap: Duration,
+    pub long_press: Duration,
+    /// How far a finger may move and still tap or long press, in logical pixels.
+    pub slop: f64,
+    /// How far a finger has to move for a swipe.
+    pub swipe_distance: f64,
+}
+
+impl Default for TouchThresholds {
+    fn default() -> Self {
+        TouchThresholds {
+            tap: Duration::from_millis(250),
+            double_tap: Duration::from_millis(400),
+            long_press: Duration::from_millis(700),
+            slop: 12.0,
+            swipe_distance: 80.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct TouchPoint {
+    started: Instant,
+    start: [f64; 2],
+    current: [f64; 2],
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+/// Recognizes gestures from touch events; like `Recognizer` it gets the time from the caller.
+#[derive(Debug, Clone, Default)]
+pub struct TouchRecognizer {
+    pub thresholds: TouchThresholds,
+    touches: HashMap<u64, TouchPoint>,
+    /// Set once more than one finger was down, until all fingers are lifted.
+    multi_touch: bool,
+    long_press_sent: bool,
+    /// Finger distance at the last pinch gesture.
+    pinch_distance: Option<f64>,
+    last_tap: Option<Instant>,
+}
+
+impl TouchRecognizer {
+    pub fn new(thresholds: TouchThresholds) -> TouchRecognizer {
+        TouchRecognizer {
+            thresholds,
+            ..TouchRecognizer::default()
+        }
+    }
+
+    /// Feed a window event, returning the gesture it completes.
+    pub fn update(&mut self, event: &Event, now: Instant) -> Option<TouchGesture> {
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Touch(touch),
+                ..
+            } => {
+                let position = [touch.location.x, touch.location.y];
+                match touch.phase {
+                    TouchPhase::Started => {
+                        self.start(touch.id, position, now);
+                        None
+                    }
+                    TouchPhase::Moved => self.moved(touch.id, position),
+                    TouchPhase::Ended => self.end(touch.id, now),
+                    TouchPhase::Cancelled => {
+                        self.cancel(touch.id);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn start(&mut self, id: u64, position: [f64; 2], now: Instant) {
+        if self.touches.is_empty() {
+            self.multi_touch = false;
+            self.long_press_sent = false;
+        }
+        self.touches.insert(
+            id,
+            TouchPoint {
+                started: now,
+                start: position,
+                current: position,
+            },
+        );
+        if self.touches.len() > 1 {
+            self.multi_touch = true;
+            self.pinch_distance = self.finger_distance();
+        }
+    }
+
+    pub fn moved(&mut self, id: u64, position: [f64; 2]) -> Option<TouchGesture> {
+        self.touches.get_mut(&id)?.current = position;
+        if self.touches.len() != 2 {
+            return None;
+        }
+        let previous = self.pinch_distance?;
+        let current = self.finger_distance()?;
+        if previous <= 0.0 || (current - previous).abs() < self.thresholds.slop {
+            return None;
+        }
+        self.pinch_distance = Some(current);
+        Some(TouchGesture::Pinch(current / previous))
+    }
+
+    pub fn end(&mut self, id: u64, now: Instant) -> Option<TouchGesture> {
+        let point = self.touches.remove(&id)?;
+        if self.touches.len() < 2 {
+            self.pinch_distance = None;
+        }
+        if self.multi_touch || self.long_press_sent {
+            return None;
+        }
+
+        let thresholds = self.thresholds;
+        let dx = point.current[0] - point.start[0];
+        let dy = point.current[1] - point.start[1];
+        if dx.abs().max(dy.abs()) >= thresholds.swipe_distance {
+            self.last_tap = None;
+            return Some(if dx.abs() > dy.abs() {
+                if dx < 0.0 {
+                    TouchGesture::SwipeLeft
+                } else {
+                    TouchGesture::SwipeRight
+                }
+            } else if dy < 0.0 {
+                TouchGesture::SwipeUp
+            } else {
+                TouchGesture::SwipeDown
+            });
+        }
+
+        if distance(point.start, point.current) > thresholds.slop
+            || now.duration_since(point.started) > thresholds.tap
+        {
+            return None;
+        }
+        match self.last_tap.take() {
+            Some(last) if now.duration_since(last) <= thresholds.double_tap => {
+                Some(TouchGesture::DoubleTap)
+            }
+            _ => {
+                self.last_tap = Some(now);
+                Some(TouchGesture::Tap)
+            }
+        }
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        self.touches.remove(&id);
+        if self.touches.len() < 2 {
+            self.pinch_distance = None;
+        }
+    }
+
+    /// When a long press will be due, if a single finger is held down.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.multi_touch || self.long_press_sent || self.touches.len() != 1 {
+            return None;
+        }
+        self.touches
+            .values()
+            .next()
+            .map(|point| point.started + self.thresholds.long_press)
+    }
+
+    /// Report a long press once a single finger has been held still long enough.
+    pub fn poll(&mut self, now: Instant) -> Option<TouchGesture> {
+        if self.multi_touch || self.long_press_sent || self.touches.len() != 1 {
+            return None;
+        }
+        let point = *self.touches.values().next()?;
+        if distance(point.start, point.current) <= self.thresholds.slop
+            && now.duration_since(point.started) >= self.thresholds.long_press
+        {
+            self.long_press_sent = true;
+            self.last_tap = None;
+            return Some(TouchGesture::LongPress);
+        }
+        None
+    }
+
+    fn finger_distance(&self) -> Option<f64> {
+        let mut points = self.touches.values();
+        let a = points.next()?;
+        let b = points.next()?;
+        Some(distance(a.current, b.current))
+    }
+}
+
+/// Maps touch gestures, other than pinching, to actions.
+#[derive(Debug, Clone)]
+pub struct TouchMap(Vec<(TouchGesture, Action)>);
+
+impl TouchMap {
+    /// The default touch bindings, with the gestures named in `config` added or replaced.
+    pub fn from_config(config: &HashMap<String, String>) -> TouchMap {
+        let mut map = vec![
+            (TouchGesture::DoubleTap, Action::ToggleFullscreen),
+            (TouchGesture::LongPress, Action::ContextMenu),
+            (TouchGesture::SwipeLeft, Action::CycleLayout),
+            (TouchGesture::SwipeRight, Action::CycleGpu),
+        ];
+        for (name, action_name) in config {
+            let gesture = match TouchGesture::parse(name) {
+                Some(gesture) => gesture,
+                None => {
+                    eprintln!("unknown touch gesture: {}", name);
+                    continue;
+                }
+            };
+            map.retain(|&(g, _)| g != gesture);
+            if action_name == "none" {
+                continue;
+            }
+            match Action::from_name(action_name) {
+                Some(action) => map.push((gesture, action)),
+                None => eprintln!("unknown action for {}: {}", name, action_name),
+            }
+        }
+        TouchMap(map)
+    }
+
+    pub fn action(&self, gesture: TouchGesture) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|&&(g, _)| g == gesture)
+            .map(|&(_, action)| action)
+    }
+}
+
+impl<'a> From<&'a TouchConfig> for TouchThresholds {
+    fn from(config: &TouchConfig) -> TouchThresholds {
+        TouchThresholds {
+            tap: Duration::from_millis(config.tap_ms),
+            double_tap: Duration::from_millis(config.double_tap_ms),
+            long_press: Duration::from_millis(config.long_press_ms),
+            slop: config.slop,
+            swipe_distance: config.swipe_distance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    /// Press `button` at `at` and release it `held` later.
+    fn click(
+        recognizer: &mut Recognizer,
+        button: MouseButton,
+        at: Instant,
+        held: Duration,
+    ) -> Option<GestureKind> {
+        recognizer.press(button, at);
+        recognizer.release(button, at + held).map(|gesture| {
+            assert_eq!(gesture.button, button);
+            gesture.kind
+        })
+    }
+
+    #[test]
+    fn click_within_the_threshold() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let left = MouseButton::Left;
+        assert_eq!(click(&mut recognizer, left, t0, ms(115)), Some(GestureKind::Click));
+        assert_eq!(click(&mut recognizer, left, t0 + ms(2000), ms(116)), None);
+    }
+
+    #[test]
+    fn double_and_triple_click() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let left = MouseButton::Left;
+        let kinds: Vec<_> = [0, 400, 800, 1200]
+            .iter()
+            .map(|&at| click(&mut recognizer, left, t0 + ms(at), ms(50)))
+            .collect();
+        let expected = [
+            Some(GestureKind::Click),
+            Some(GestureKind::DoubleClick),
+            Some(GestureKind::TripleClick),
+            Some(GestureKind::Click),
+        ];
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn clicks_too_far_apart_are_single() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let left = MouseButton::Left;
+        assert_eq!(click(&mut recognizer, left, t0, ms(50)), Some(GestureKind::Click));
+        let late = click(&mut recognizer, left, t0 + ms(700), ms(50));
+        assert_eq!(late, Some(GestureKind::Click));
+    }
+
+    #[test]
+    fn slow_release_resets_the_count() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let left = MouseButton::Left;
+        assert_eq!(click(&mut recognizer, left, t0, ms(50)), Some(GestureKind::Click));
+        assert_eq!(click(&mut recognizer, left, t0 + ms(100), ms(200)), None);
+        let next = click(&mut recognizer, left, t0 + ms(350), ms(50));
+        assert_eq!(next, Some(GestureKind::Click));
+    }
+
+    #[test]
+    fn long_press() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let right = MouseButton::Right;
+        assert_eq!(recognizer.deadline(), None);
+        recognizer.press(right, t0);
+        assert_eq!(recognizer.deadline(), Some(t0 + ms(700)));
+        assert_eq!(recognizer.poll(t0 + ms(699)), None);
+        let gesture = recognizer.poll(t0 + ms(700));
+        assert_eq!(
+            gesture,
+            Some(Gesture {
+                button: right,
+                kind: GestureKind::LongPress,
+            })
+        );
+        assert_eq!(recognizer.deadline(), None);
+        assert_eq!(recognizer.poll(t0 + ms(800)), None);
+        assert_eq!(recognizer.release(right, t0 + ms(900)), None);
+    }
+
+    #[test]
+    fn buttons_are_independent() {
+        let mut recognizer = Recognizer::default();
+        let t0 = Instant::now();
+        let (left, right) = (MouseButton::Left, MouseButton::Right);
+        assert_eq!(click(&mut recognizer, left, t0, ms(50)), Some(GestureKind::Click));
+        let other = click(&mut recognizer, right, t0 + ms(100), ms(50));
+        assert_eq!(other, Some(GestureKind::Click));
+        let second = click(&mut recognizer, left, t0 + ms(200), ms(50));
+        assert_eq!(second, Some(GestureKind::DoubleClick));
+
+        // Holding one button does not stop clicks with another.
+        recognizer.press(right, t0 + ms(1000));
+        let third = click(&mut recognizer, left, t0 + ms(1100), ms(50));
+        assert_eq!(third, Some(GestureKind::Click));
+        assert_eq!(recognizer.deadline(), Some(t0 + ms(1700)));
+    }
+
+    #[test]
+    fn parse_gestures() {
+        assert_eq!(
+            Gesture::parse("left_click"),
+            Some(Gesture {
+                button: MouseButton::Left,
+                kind: GestureKind::Click,
+            })
+        );
+        assert_eq!(
+            Gesture::parse("middle_double_click"),
+            Some(Gesture {
+                button: MouseButton::Middle,
+                kind: GestureKind::DoubleClick,
+            })
+        );
+        assert_eq!(
+            Gesture::parse("right_long_press"),
+            Some(Gesture {
+                button: MouseButton::Right,
+                kind: GestureKind::LongPress,
+            })
+        );
+        assert_eq!(Gesture::parse("left"), None);
+        assert_eq!(Gesture::parse("left_quadruple_click"), None);
+        assert_eq!(Gesture::parse("back_click"), None);
+    }
+
+    #[test]
+    fn gesture_map_from_config() {
+        let gesture = |button, kind| Gesture { button, kind };
+        let double = gesture(MouseButton::Left, GestureKind::DoubleClick);
+        let right = gesture(MouseButton::Right, GestureKind::Click);
+        let middle = gesture(MouseButton::Middle, GestureKind::Click);
+
+        let defaults = GestureMap::from_config(&HashMap::new());
+        assert_eq!(defaults.action(double), Some(Action::ToggleFullscreen));
+        assert_eq!(defaults.action(right), Some(Action::ContextMenu));
+        assert_eq!(defaults.action(middle), None);
+
+        let config: HashMap<String, String> = [
+            ("left_double_click", "none"),
+            ("middle_click", "quit"),
+            ("left_click", "no_such_action"),
+            ("left_wiggle", "quit"),
+        ]
+        .iter()
+        .map(|&(gesture, action)| (gesture.to_string(), action.to_string()))
+        .collect();
+        let map = GestureMap::from_config(&config);
+        assert_eq!(map.action(double), None);
+        assert_eq!(map.action(right), Some(Action::ContextMenu));
+        assert_eq!(map.action(middle), Some(Action::Quit));
+        assert_eq!(map.action(gesture(MouseButton::Left, GestureKind::Click)), None);
+    }
+
+    /// A finger put down at `start` at `at`, moved to `end` and lifted `held` later.
+    fn touch(
+        recognizer: &mut TouchRecognizer,
+        start: [f64; 2],
+        end: [f64; 2],
+        at: Instant,
+        held: Duration,
+    ) -> Option<TouchGesture> {
+        recognizer.start(1, start, at);
+        assert_eq!(recognizer.moved(1, end), None);
+        recognizer.end(1, at + held)
+    }
+
+    #[test]
+    fn tap_and_double_tap() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        let p = [100.0, 100.0];
+        assert_eq!(touch(&mut recognizer, p, p, t0, ms(100)), Some(TouchGesture::Tap));
+        let second = touch(&mut recognizer, p, p, t0 + ms(300), ms(50));
+        assert_eq!(second, Some(TouchGesture::DoubleTap));
+        let third = touch(&mut recognizer, p, p, t0 + ms(500), ms(50));
+        assert_eq!(third, Some(TouchGesture::Tap));
+        let late = touch(&mut recognizer, p, p, t0 + ms(1500), ms(50));
+        assert_eq!(late, Some(TouchGesture::Tap));
+    }
+
+    #[test]
+    fn slow_touch_is_no_tap() {
+        let mut recognizer = TouchRecognizer::default();
+        let p = [100.0, 100.0];
+        assert_eq!(touch(&mut recognizer, p, p, Instant::now(), ms(251)), None);
+    }
+
+    #[test]
+    fn moving_past_the_slop_cancels_the_tap() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        let start = [100.0, 100.0];
+        let within = touch(&mut recognizer, start, [108.0, 108.0], t0, ms(50));
+        assert_eq!(within, Some(TouchGesture::Tap));
+        let past = touch(&mut recognizer, start, [109.0, 109.0], t0 + ms(1000), ms(50));
+        assert_eq!(past, None);
+    }
+
+    #[test]
+    fn swipes() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        let start = [200.0, 200.0];
+        let swipes = [
+            ([120.0, 210.0], TouchGesture::SwipeLeft),
+            ([280.0, 190.0], TouchGesture::SwipeRight),
+            ([210.0, 120.0], TouchGesture::SwipeUp),
+            ([190.0, 280.0], TouchGesture::SwipeDown),
+        ];
+        for (i, &(end, gesture)) in swipes.iter().enumerate() {
+            let at = t0 + ms(100 * i as u64);
+            assert_eq!(touch(&mut recognizer, start, end, at, ms(400)), Some(gesture));
+        }
+        let short = touch(&mut recognizer, start, [121.0, 200.0], t0 + ms(1000), ms(50));
+        assert_eq!(short, None);
+    }
+
+    #[test]
+    fn touch_long_press() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        recognizer.start(1, [100.0, 100.0], t0);
+        assert_eq!(recognizer.deadline(), Some(t0 + ms(700)));
+        assert_eq!(recognizer.poll(t0 + ms(699)), None);
+        assert_eq!(recognizer.poll(t0 + ms(700)), Some(TouchGesture::LongPress));
+        assert_eq!(recognizer.deadline(), None);
+        assert_eq!(recognizer.poll(t0 + ms(800)), None);
+        assert_eq!(recognizer.end(1, t0 + ms(900)), None);
+    }
+
+    #[test]
+    fn moved_finger_does_not_long_press() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        recognizer.start(1, [100.0, 100.0], t0);
+        recognizer.moved(1, [120.0, 100.0]);
+        assert_eq!(recognizer.poll(t0 + ms(1000)), None);
+    }
+
+    #[test]
+    fn pinch() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        recognizer.start(1, [0.0, 0.0], t0);
+        recognizer.start(2, [100.0, 0.0], t0 + ms(10));
+        assert_eq!(recognizer.deadline(), None);
+        assert_eq!(recognizer.moved(2, [150.0, 0.0]), Some(TouchGesture::Pinch(1.5)));
+        // Less than the slop since the last pinch.
+        assert_eq!(recognizer.moved(2, [155.0, 0.0]), None);
+        assert_eq!(recognizer.moved(1, [80.0, 0.0]), Some(TouchGesture::Pinch(0.5)));
+        // Lifting the fingers after a pinch is no tap.
+        assert_eq!(recognizer.end(2, t0 + ms(100)), None);
+        assert_eq!(recognizer.end(1, t0 + ms(110)), None);
+    }
+
+    #[test]
+    fn cancel_ends_the_pinch() {
+        let mut recognizer = TouchRecognizer::default();
+        let t0 = Instant::now();
+        recognizer.start(1, [0.0, 0.0], t0);
+        recognizer.start(2, [100.0, 0.0], t0);
+        assert_eq!(recognizer.pinch_distance, Some(100.0));
+        recognizer.cancel(2);
+        assert_eq!(recognizer.pinch_distance, None);
+        assert_eq!(recognizer.moved(1, [50.0, 0.0]), None);
+    }
+}
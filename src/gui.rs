@@ -1,23 +1,24 @@
-//! This crate is used for sharing a few items between the conrod examples.
+//! The clock's state and its conrod UI.
 //!
-//! The module contains:
-//!
-//! - `pub struct DemoApp` as a demonstration of some state we want to change.
-//! - `pub fn gui` as a demonstration of all widgets, some of which mutate our `DemoApp`.
-//! - `pub struct Ids` - a set of all `widget::Id`s used in the `gui` fn.
-//!
-//! By sharing these items between these examples, we can test and ensure that the different events
-//! and drawing backends behave in the same manner.
-#![allow(dead_code)]
-
-extern crate rand;
+//! - `pub struct App` holds everything the window shows: the clock, stopwatch and timer, the
+//!   layouts, the latest metrics and their history, and the theme, scale and overlays. Its
+//!   `handle` method carries out an `Action`.
+//! - `pub fn gui` draws the `App` and returns the actions picked from the context menu.
+//! - `pub struct Ids` - the `widget::Id`s used in the `gui` fn.
+use std::time::{Duration, Instant};
 
-// use my_widgets;
+use actions::Action;
+use config::Config;
+use fonts::Fonts;
+use layout::Layout;
+use locale::Locale;
+use metrics::{History, Metric, Snapshot};
+use my_widgets::ClockPanel;
+use night::NightSchedule;
+use theme::{self, Palette};
+use units::UnitFormat;
 
-pub const WIN_W: u32 = 600;
-pub const WIN_H: u32 = 420;
-
-/// A demonstration of some application state we want to control with a conrod GUI.
+/// The local time shown in clock mode, refreshed every frame.
 pub struct Clock(chrono::DateTime<chrono::Local>);
 
 impl Clock {
@@ -31,17 +32,244 @@ impl Clock {
     }
 }
 
+/// What the big text shows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Clock,
+    Stopwatch,
+    Timer,
+}
+
+impl Mode {
+    pub fn next(self) -> Mode {
+        match self {
+            Mode::Clock => Mode::Stopwatch,
+            Mode::Stopwatch => Mode::Timer,
+            Mode::Timer => Mode::Clock,
+        }
+    }
+}
+
+/// Measures time while running; used for both the stopwatch and the countdown timer.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Stopwatch {
+    started: Option<Instant>,
+    elapsed: Duration,
+}
+
+impl Stopwatch {
+    pub fn toggle(&mut self) {
+        match self.started.take() {
+            Some(started) => self.elapsed += started.elapsed(),
+            None => self.started = Some(Instant::now()),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Stopwatch::default();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed + self.started.map(|s| s.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Everything the `gui` needs besides the widget ids.
+pub struct App {
+    pub clock: Clock,
+    pub night: NightSchedule,
+    pub fonts: Fonts,
+    pub mode: Mode,
+    /// The layouts to cycle through, and the index of the one shown.
+    pub layouts: Vec<Layout>,
+    pub layout: usize,
+    pub stopwatch: Stopwatch,
+    pub timer: Stopwatch,
+    pub timer_duration: Duration,
+    /// Index into `theme::THEMES`.
+    pub theme: usize,
+    pub show_hardware: bool,
+    /// Show the statistics under each metric.
+    pub show_statistics: bool,
+    pub show_help: bool,
+    /// Key bindings and their actions, shown in the help overlay.
+    pub help: Vec<(String, Action)>,
+    /// Index of the graphics card shown.
+    pub gpu: u32,
+    /// Names of all graphics cards, for the context menu.
+    pub gpus: Vec<String>,
+    /// Where the context menu was opened, while it is open.
+    pub menu: Option<conrod_core::Point>,
+    /// Text scale set by pinching.
+    pub scale: f64,
+    /// The latest sample from the metrics thread.
+    pub metrics: Snapshot,
+    /// Earlier samples, for the statistics.
+    pub history: History,
+    pub locale: Locale,
+    pub units: UnitFormat,
+}
+
+impl App {
+    pub fn new(config: &Config, fonts: Fonts, help: Vec<(String, Action)>) -> App {
+        let locale = Locale::new(config.language.as_ref().map(String::as_str));
+        let mut units = UnitFormat::from_config(&config.units);
+        units.decimal_separator = locale.decimal_separator();
+        App {
+            clock: Clock::new(),
+            night: NightSchedule::from_config(&config.night),
+            fonts,
+            mode: Mode::Clock,
+            layouts: Layout::from_config(&config.layouts),
+            layout: 0,
+            stopwatch: Stopwatch::default(),
+            timer: Stopwatch::default(),
+            timer_duration: Duration::from_secs(config.timer.minutes as u64 * 60),
+            theme: 0,
+            show_hardware: true,
+            show_statistics: config.statistics.show,
+            show_help: false,
+            help,
+            gpu: 0,
+            gpus: Vec::new(),
+            menu: None,
+            scale: 1.0,
+            metrics: Snapshot::default(),
+            history: History::new(
+                &config.statistics,
+                Duration::from_millis(config.metrics.interval_ms),
+            ),
+            units,
+            locale,
+        }
+    }
+
+    pub fn zoom(&mut self, factor: f64) {
+        self.scale = (self.scale * factor).max(0.25).min(4.0);
+    }
+
+    /// `size` scaled by the pinch zoom.
+    pub fn font_size(&self, size: conrod_core::FontSize) -> conrod_core::FontSize {
+        (size as f64 * self.scale).round() as conrod_core::FontSize
+    }
+
+    /// Apply an action that changes what is shown; window actions are handled by the main thread.
+    pub fn handle(&mut self, action: Action) {
+        match action {
+            Action::CycleLayout => self.layout = (self.layout + 1) % self.layouts.len().max(1),
+            Action::SwitchMode => self.mode = self.mode.next(),
+            Action::ToggleTimer => match self.mode {
+                Mode::Stopwatch => self.stopwatch.toggle(),
+                Mode::Timer => self.timer.toggle(),
+                Mode::Clock => (),
+            },
+            Action::ResetTimer => match self.mode {
+                Mode::Stopwatch => self.stopwatch.reset(),
+                Mode::Timer => self.timer.reset(),
+                Mode::Clock => (),
+            },
+            Action::ToggleHardware => self.show_hardware = !self.show_hardware,
+            Action::ToggleStatistics => self.show_statistics = !self.show_statistics,
+            Action::ResetStatistics => self.history.reset(),
+            Action::NextTheme => self.theme = (self.theme + 1) % theme::THEMES.len(),
+            Action::ToggleHelp => self.show_help = !self.show_help,
+            _ => (),
+        }
+    }
+
+    /// The statistics line shown under `metric`, `None` when hidden or not a number.
+    pub fn statistics(&self, metric: Metric) -> Option<String> {
+        if !self.show_statistics {
+            return None;
+        }
+        let stats = self.history.stats(metric)?;
+        Some(stats.line(&self.locale, &self.units))
+    }
+
+    /// How long until the display changes by itself: the next second of the clock, or the next
+    /// tenth of a running stopwatch.
+    pub fn until_next_tick(&self) -> Duration {
+        const SECOND: u64 = 1_000_000_000;
+        let wall = until_next(chrono::Local::now().timestamp_subsec_nanos() as u64, SECOND);
+        match self.mode {
+            Mode::Stopwatch if self.stopwatch.is_running() => {
+                let elapsed = self.stopwatch.elapsed().subsec_nanos() as u64;
+                until_next(elapsed, SECOND / 10).min(wall)
+            }
+            Mode::Timer if self.timer.is_running() => {
+                // The remaining time is shown rounded down, so it changes when the fraction wraps.
+                let remaining = self
+                    .timer_duration
+                    .checked_sub(self.timer.elapsed())
+                    .unwrap_or_default();
+                let fraction = remaining.subsec_nanos() as u64;
+                until_next(SECOND - fraction, SECOND).min(wall)
+            }
+            _ => wall,
+        }
+    }
+
+    /// How far into the night it is, from `0.0` for day to `1.0` for full night.
+    pub fn night_level(&self) -> f32 {
+        self.night.level(&self.clock.0)
+    }
+
+    /// The current theme, blended towards the night theme as the night sets in.
+    pub fn palette(&self) -> Palette {
+        let night = Palette::night(self.night.brightness);
+        theme::THEMES[self.theme].blend(&night, self.night_level())
+    }
+
+    /// Today's date, with the weekday and month in the user's language.
+    pub fn date_text(&self) -> String {
+        self.locale.date(&self.clock.0)
+    }
+
+    /// The text for the big display in the current mode.
+    pub fn time_text(&self) -> String {
+        match self.mode {
+            Mode::Clock => format!("{}", self.clock),
+            Mode::Stopwatch => format_duration_tenths(self.stopwatch.elapsed()),
+            Mode::Timer => format_duration(
+                self.timer_duration
+                    .checked_sub(self.timer.elapsed())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+/// Like `format_duration`, with tenths of a second.
+fn format_duration_tenths(d: Duration) -> String {
+    format!("{}.{}", format_duration(d), d.subsec_millis() / 100)
+}
+
+/// Time until the next multiple of `step` nanoseconds, given how far into the current one we are.
+fn until_next(into: u64, step: u64) -> Duration {
+    // Wake up just after the boundary rather than just before it.
+    Duration::from_nanos(step - into % step) + Duration::from_millis(1)
+}
+
 impl std::fmt::Display for Clock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0.format("%k:%M:%S"))
     }
 }
 
-/// A set of reasonable stylistic defaults that works for the `gui` below.
+/// The conrod theme for widgets that don't pick their own colours and sizes.
 pub fn theme() -> conrod_core::Theme {
     use conrod_core::position::{Align, Direction, Padding, Position, Relative};
     conrod_core::Theme {
-        name: "Demo Theme".to_string(),
+        name: "Clock".to_string(),
         padding: Padding::none(),
         x_position: Position::Relative(Relative::Align(Align::Start), None),
         y_position: Position::Relative(Relative::Direction(Direction::Backwards, 20.0), None),
@@ -63,170 +291,137 @@ pub fn theme() -> conrod_core::Theme {
 // Generate a unique `WidgetId` for each widget.
 conrod_core::widget_ids! {
     pub struct Ids {
-        // The scrollable canvas.
+        // The background.
         canvas,
-        // The title and introduction widgets.
-        title,
-        card_info1,
-        card_info2,
-        card_info3,
-        temperature,
-        fan_speed,
-        memory_used,
-        memory_free,
-        memory_total,
-        memory_utilization,
-        gpu_utilization,
-        power_usage,
-        circle,
-    }
-}
-
-macro_rules! str_line {
-    ($k:ident) => {
-        if $k < 100000 {
-            format!("{}: {}", stringify!($k).split('_').collect::<Vec<&str>>().join(" "), $k)
-        } else {
-            format!("{}: {:.4e}", stringify!($k).split('_').collect::<Vec<&str>>().join(" "), $k as f64)
-        }
-    };
-    ($k:ident, $u:expr) => {
-        if $k < 100000 {
-            format!("{}: {}{}", stringify!($k).split('_').collect::<Vec<&str>>().join(" "), $k, $u)
-        } else {
-            format!("{}: {:.4e}{}", stringify!($k).split('_').collect::<Vec<&str>>().join(" "), $k as f64, $u)
-        }
-    };
+        // The clock and the hardware panel.
+        panel,
+        help_background,
+        help_text,
+        menu_items[],
+    }
 }
 
-/// Instantiate a GUI demonstrating every widget available in conrod.
-pub fn gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut Clock, card: &nvml_wrapper::Device) {
-    use conrod_core::{widget, Positionable, Widget};
+const MARGIN: conrod_core::Scalar = 30.0;
+const FONT_SIZE: conrod_core::FontSize = 24;
 
-    const MARGIN: conrod_core::Scalar = 30.0;
-    const TITLE_SIZE: conrod_core::FontSize = 120;
-    const FONT_SIZE: conrod_core::FontSize = 24;
+/// Draw the clock panel, and the help overlay and context menu when they are open.
+///
+/// Returns the actions picked from the context menu.
+pub fn gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut App) -> Vec<Action> {
+    use conrod_core::{widget, Colorable, Positionable, Sizeable, Widget};
 
-    // `Canvas` is a widget that provides some basic functionality for laying out children widgets.
-    // By default, its size is the size of the window. We'll use this as a background for the
-    // following widgets, as well as a scrollable container for the children widgets.
-    // const TITLE: &'static str = "All Widgets";
+    app.clock.update();
+    let palette = app.palette();
 
     widget::Canvas::new()
         .pad(MARGIN)
-        .scroll_kids_vertically()
+        .color(palette.background)
         .set(ids.canvas, ui);
-    ////////////////
-    ///// TEXT /////
-    ////////////////
-
-    app.update();
-    // We'll demonstrate the `Text` primitive widget by using it to draw a title and an
-    // introduction to the example.
-    let time = format!("{}", app);
-    widget::Text::new(&time)
-        .font_size(TITLE_SIZE)
-        // .mid_top_of(ids.canvas)
+    ClockPanel::new(app)
+        .padded_wh_of(ids.canvas, MARGIN)
         .middle_of(ids.canvas)
-        .set(ids.title, ui);
-
-    let memory_clock = card
-        .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Memory)
-        .unwrap_or(0);
-    let graphics_clock = card
-        .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Graphics)
-        .unwrap_or(0);
-    let video_clock = card
-        .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Video)
-        .unwrap_or(0);
-    let temperature = card
-        .temperature(nvml_wrapper::enum_wrappers::device::TemperatureSensor::Gpu)
-        .unwrap_or(0);
-    let fan_speed = card.fan_speed().unwrap_or(0);
-
-    let nvml_wrapper::struct_wrappers::device::MemoryInfo {
-        used: mut memory_used,
-        free: mut memory_free,
-        total: mut memory_total,
-    } = card
-        .memory_info()
-        .unwrap_or(nvml_wrapper::struct_wrappers::device::MemoryInfo {
-            free: 0,
-            total: 0,
-            used: 0,
-        });
-    // memory_used/=10u64.pow(6);
-    // memory_free/=10u64.pow(6);
-    // memory_total/=10u64.pow(6);
-    memory_used/=1024u64.pow(2);
-    memory_free/=1024u64.pow(2);
-    memory_total/=1024u64.pow(2);
-    let nvml_wrapper::struct_wrappers::device::Utilization {
-        memory: memory_utilization,
-        gpu: gpu_utilization,
-    } = card
-        .utilization_rates()
-        .unwrap_or(nvml_wrapper::struct_wrappers::device::Utilization { memory: 0, gpu: 0 });
-
-    let power_usage = card.power_usage().unwrap_or(0)/1000;
-
-    widget::Text::new(&str_line!(memory_used, " MB"))
-        .font_size(FONT_SIZE)
-        // .mid_top_of(ids.canvas)
-        .bottom_left_of(ids.canvas)
-        .set(ids.memory_used, ui);
-    widget::Text::new(&str_line!(memory_free, " MB"))
-        .font_size(FONT_SIZE)
-        // .mid_top_of(ids.canvas)
-        .mid_bottom_of(ids.canvas)
-        .set(ids.memory_free, ui);
-    widget::Text::new(&str_line!(memory_total, " MB"))
-        .font_size(FONT_SIZE)
-        // .mid_top_of(ids.canvas)
-        .bottom_right_of(ids.canvas)
-        .set(ids.memory_total, ui);
-
-    widget::Text::new(&str_line!(memory_clock, " MHz"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.memory_used, MARGIN)
-        .set(ids.card_info1, ui);
-    widget::Text::new(&str_line!(graphics_clock, " MHz"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.memory_free, MARGIN)
-        .set(ids.card_info2, ui);
-    widget::Text::new(&str_line!(video_clock, " MHz"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.memory_total, MARGIN)
-        .set(ids.card_info3, ui);
-
-    widget::Text::new(&str_line!(temperature, " °C"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.card_info1, MARGIN)
-        .set(ids.temperature, ui);
-    widget::Text::new(&str_line!(fan_speed, "%"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.card_info2, MARGIN)
-        .set(ids.fan_speed, ui);
-    widget::Text::new(&str_line!(power_usage, " W"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.card_info3, MARGIN)
-        .set(ids.power_usage, ui);
-
-    widget::Text::new(&str_line!(memory_utilization, "%"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.temperature, MARGIN)
-        .set(ids.memory_utilization, ui);
-    widget::Text::new(&str_line!(gpu_utilization, "%"))
-        .font_size(FONT_SIZE)
-        .y_relative_to(ids.power_usage, MARGIN)
-        .set(ids.gpu_utilization, ui);
-
-
-    // for event in my_widgets::CircularButton::new()
-    //     .middle_of(ids.canvas)
-    //     .set(ids.circle, ui)
-    // {
-    //     println!("Click!");
-
-    // }
+        .set(ids.panel, ui);
+
+    if app.show_help {
+        let help: String = app
+            .help
+            .iter()
+            .map(|&(ref keys, action)| format!("{}: {}\n", keys, app.locale.action(action)))
+            .collect();
+        widget::Rectangle::fill_with(ui.window_dim(), palette.background.alpha(0.9))
+            .middle_of(ids.canvas)
+            .set(ids.help_background, ui);
+        widget::Text::new(&help)
+            .font_size(FONT_SIZE)
+            .color(palette.text)
+            .middle_of(ids.canvas)
+            .set(ids.help_text, ui);
+    }
+
+    // Clicking anywhere outside the menu closes it.
+    let clicked = [ids.canvas, ids.panel]
+        .iter()
+        .any(|&id| ui.widget_input(id).clicks().left().next().is_some());
+    if clicked {
+        app.menu = None;
+    }
+    match app.menu {
+        Some(position) => {
+            let picked = context_menu(ui, ids, app, position, &palette);
+            if picked.is_some() {
+                app.menu = None;
+            }
+            picked.into_iter().collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// The entries of the context menu.
+fn menu_items(app: &App) -> Vec<(String, Action)> {
+    let locale = &app.locale;
+    let mut items = vec![
+        (locale.menu("fullscreen", &[]), Action::ToggleFullscreen),
+        (locale.menu("always_on_top", &[]), Action::ToggleAlwaysOnTop),
+        (locale.menu("next_theme", &[]), Action::NextTheme),
+        (locale.menu("next_layout", &[]), Action::CycleLayout),
+        (locale.menu("mode", &[("mode", locale.mode(app.mode))]), Action::SwitchMode),
+    ];
+    if app.mode != Mode::Clock {
+        items.push((locale.menu("start_stop", &[]), Action::ToggleTimer));
+        items.push((locale.menu("reset", &[]), Action::ResetTimer));
+    }
+    for (i, name) in app.gpus.iter().enumerate() {
+        let marker = if i as u32 == app.gpu { "> " } else { "" };
+        let index = i.to_string();
+        let label = locale.menu("gpu", &[("index", index.as_str()), ("name", name.as_str())]);
+        items.push((format!("{}{}", marker, label), Action::SelectGpu(i as u32)));
+    }
+    items.push((locale.menu("quit", &[]), Action::Quit));
+    items
+}
+
+/// Draw the context menu with its top left corner at `position`, kept inside the window.
+fn context_menu(
+    ui: &mut conrod_core::UiCell,
+    ids: &mut Ids,
+    app: &App,
+    position: conrod_core::Point,
+    palette: &Palette,
+) -> Option<Action> {
+    use conrod_core::{widget, Borderable, Colorable, Labelable, Positionable, Sizeable, Widget};
+
+    const ITEM_W: conrod_core::Scalar = 320.0;
+    const ITEM_H: conrod_core::Scalar = 44.0;
+
+    let items = menu_items(app);
+    if ids.menu_items.len() < items.len() {
+        ids.menu_items
+            .resize(items.len(), &mut ui.widget_id_generator());
+    }
+
+    let [win_w, win_h] = ui.window_dim();
+    let height = ITEM_H * items.len() as conrod_core::Scalar;
+    let left = position[0].min(win_w / 2.0 - ITEM_W).max(-win_w / 2.0);
+    let top = position[1].max(-win_h / 2.0 + height).min(win_h / 2.0);
+
+    let mut picked = None;
+    for (i, (label, action)) in items.iter().enumerate() {
+        let y = top - ITEM_H * (i as conrod_core::Scalar + 0.5);
+        let clicked = widget::Button::new()
+            .label(label)
+            .label_font_size(FONT_SIZE)
+            .label_color(palette.text)
+            .color(palette.background)
+            .border(1.0)
+            .border_color(palette.text)
+            .w_h(ITEM_W, ITEM_H)
+            .x_y(left + ITEM_W / 2.0, y)
+            .set(ids.menu_items[i], ui)
+            .was_clicked();
+        if clicked {
+            picked = Some(*action);
+        }
+    }
+    picked
 }
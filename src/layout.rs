@@ -0,0 +1,364 @@
+//! Arranging the panels of the clock from a layout description.
+//!
+//! A layout is a tree of rows, columns and grids with panels (the clock, a metric, a list or
+//! nothing) as leaves, read from the `[[layouts]]` config sections. `Layout::cells` divides a
+//! rectangle between the leaves according to their relative sizes. Text in the first column of a
+//! row is aligned left, in the last column right and everywhere else centred.
+
+use conrod_core::{Range, Rect, Scalar};
+use serde::Deserialize;
+
+use config::{LayoutConfig, LayoutNode};
+use metrics::Metric;
+
+/// The layouts used when the config has none.
+const DEFAULT_LAYOUTS: &str = r#"
+[[layouts]]
+name = "standard"
+root = { rows = [
+    { panel = "date", size = 4 },
+    { panel = "clock", size = 24 },
+    { size = 5, columns = 3, grid = [
+        "memory_utilization", "empty", "gpu_utilization",
+        "temperature", "fan_speed", "power_usage",
+        "memory_clock", "graphics_clock", "video_clock",
+        "memory_used", "memory_free", "memory_total",
+        "battery", "battery_time", "system_power",
+    ] },
+] }
+
+[[layouts]]
+name = "clock_only"
+root = { panel = "clock", scale = 1.5 }
+"#;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Panel {
+    Empty,
+    Clock,
+    Date,
+    /// The processes using the graphics card, one per line.
+    Processes,
+    /// Receive and send rates, one line per network interface.
+    Network,
+    /// Read and write rates, one line per disk.
+    Disks,
+    /// Used and total space, one line per filesystem.
+    Filesystems,
+    Metric(Metric),
+}
+
+impl Panel {
+    fn from_name(name: &str) -> Panel {
+        match name {
+            "" | "empty" => Panel::Empty,
+            "clock" => Panel::Clock,
+            "date" => Panel::Date,
+            "processes" => Panel::Processes,
+            "network" => Panel::Network,
+            "disks" => Panel::Disks,
+            "filesystems" => Panel::Filesystems,
+            name => match Metric::from_name(name) {
+                Some(metric) => Panel::Metric(metric),
+                None => {
+                    eprintln!("unknown panel in layout: {}", name);
+                    Panel::Empty
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Where a panel goes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cell {
+    pub panel: Panel,
+    pub rect: Rect,
+    pub align: Align,
+    /// Text scale of the panel.
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Panel { panel: Panel, size: f64, scale: f64 },
+    Rows { rows: Vec<Node>, size: f64 },
+    Columns { columns: Vec<Node>, size: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub name: String,
+    root: Node,
+}
+
+#[derive(Deserialize)]
+struct Defaults {
+    layouts: Vec<LayoutConfig>,
+}
+
+impl Layout {
+    /// The configured layouts, or the built in ones when none are configured.
+    pub fn from_config(layouts: &[LayoutConfig]) -> Vec<Layout> {
+        if layouts.is_empty() {
+            let defaults: Defaults = toml::from_str(DEFAULT_LAYOUTS).unwrap();
+            return defaults.layouts.iter().map(Layout::new).collect();
+        }
+        layouts.iter().map(Layout::new).collect()
+    }
+
+    fn new(config: &LayoutConfig) -> Layout {
+        Layout {
+            name: config.name.clone(),
+            root: Node::new(&config.root),
+        }
+    }
+
+    /// Divide `rect` between the panels of the layout.
+    pub fn cells(&self, rect: Rect) -> Vec<Cell> {
+        let mut cells = Vec::new();
+        self.root.place(rect, Align::Center, &mut cells);
+        cells
+    }
+}
+
+impl Node {
+    fn new(config: &LayoutNode) -> Node {
+        match config {
+            LayoutNode::Panel { panel, size, scale } => Node::Panel {
+                panel: Panel::from_name(panel),
+                size: *size,
+                scale: *scale,
+            },
+            LayoutNode::Rows { rows, size } => Node::Rows {
+                rows: rows.iter().map(Node::new).collect(),
+                size: *size,
+            },
+            LayoutNode::Columns { columns, size } => Node::Columns {
+                columns: columns.iter().map(Node::new).collect(),
+                size: *size,
+            },
+            LayoutNode::Grid {
+                grid,
+                columns,
+                size,
+            } => {
+                let leaf = |name: &String| Node::Panel {
+                    panel: Panel::from_name(name),
+                    size: 1.0,
+                    scale: 1.0,
+                };
+                Node::Rows {
+                    rows: grid
+                        .chunks((*columns).max(1))
+                        .map(|row| Node::Columns {
+                            columns: row.iter().map(leaf).collect(),
+                            size: 1.0,
+                        })
+                        .collect(),
+                    size: *size,
+                }
+            }
+        }
+    }
+
+    fn size(&self) -> f64 {
+        match self {
+            Node::Panel { size, .. } | Node::Rows { size, .. } | Node::Columns { size, .. } => {
+                size.max(0.0)
+            }
+        }
+    }
+
+    fn place(&self, rect: Rect, align: Align, cells: &mut Vec<Cell>) {
+        match self {
+            Node::Panel { panel, scale, .. } => cells.push(Cell {
+                panel: *panel,
+                rect,
+                align,
+                scale: *scale,
+            }),
+            Node::Rows { rows, .. } => {
+                // Rows go from the top down, while conrod's y axis points up.
+                for (row, (bottom, top)) in rows.iter().zip(split(rect.y, rows, true)) {
+                    let y = Range::new(bottom, top);
+                    row.place(Rect { x: rect.x, y }, align, cells);
+                }
+            }
+            Node::Columns { columns, .. } => {
+                let last = columns.len().saturating_sub(1);
+                for (i, (column, (left, right))) in
+                    columns.iter().zip(split(rect.x, columns, false)).enumerate()
+                {
+                    let align = match i {
+                        _ if last == 0 => align,
+                        0 => Align::Left,
+                        i if i == last => Align::Right,
+                        _ => Align::Center,
+                    };
+                    let x = Range::new(left, right);
+                    column.place(Rect { x, y: rect.y }, align, cells);
+                }
+            }
+        }
+    }
+}
+
+/// Split `range` between `nodes` by their sizes, from the end of the range when `reverse`.
+fn split(range: Range, nodes: &[Node], reverse: bool) -> Vec<(Scalar, Scalar)> {
+    let total: f64 = nodes.iter().map(Node::size).sum();
+    let length = range.len();
+    let mut start = if reverse { range.end } else { range.start };
+    nodes
+        .iter()
+        .map(|node| {
+            let part = if total > 0.0 {
+                length * node.size() / total
+            } else {
+                0.0
+            };
+            if reverse {
+                start -= part;
+                (start, start + part)
+            } else {
+                start += part;
+                (start - part, start)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The layouts in `text`, written like the config's `[[layouts]]` sections.
+    fn layouts(text: &str) -> Vec<Layout> {
+        let layouts: Defaults = toml::from_str(text).unwrap();
+        Layout::from_config(&layouts.layouts)
+    }
+
+    fn rect(left: Scalar, bottom: Scalar, right: Scalar, top: Scalar) -> Rect {
+        Rect {
+            x: Range::new(left, right),
+            y: Range::new(bottom, top),
+        }
+    }
+
+    fn cell(panel: Panel, rect: Rect, align: Align) -> Cell {
+        Cell {
+            panel,
+            rect,
+            align,
+            scale: 1.0,
+        }
+    }
+
+    #[test]
+    fn rows_split_by_size_from_the_top() {
+        let layouts = layouts(
+            r#"
+            [[layouts]]
+            name = "rows"
+            root = { rows = [{ panel = "clock", size = 3 }, { panel = "date" }] }
+            "#,
+        );
+        let cells = layouts[0].cells(rect(0.0, 0.0, 200.0, 400.0));
+        let expected = [
+            cell(Panel::Clock, rect(0.0, 100.0, 200.0, 400.0), Align::Center),
+            cell(Panel::Date, rect(0.0, 0.0, 200.0, 100.0), Align::Center),
+        ];
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn columns_split_by_size_and_align_outwards() {
+        let layouts = layouts(
+            r#"
+            [[layouts]]
+            name = "columns"
+            root = { columns = [
+                { panel = "temperature" },
+                { panel = "fan_speed", size = 2 },
+                { panel = "power_usage" },
+            ] }
+            "#,
+        );
+        let cells = layouts[0].cells(rect(0.0, 0.0, 400.0, 100.0));
+        let metric = Panel::Metric;
+        let expected = [
+            cell(metric(Metric::Temperature), rect(0.0, 0.0, 100.0, 100.0), Align::Left),
+            cell(metric(Metric::FanSpeed), rect(100.0, 0.0, 300.0, 100.0), Align::Center),
+            cell(metric(Metric::PowerUsage), rect(300.0, 0.0, 400.0, 100.0), Align::Right),
+        ];
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn grid_fills_rows_with_a_partial_last_row() {
+        let layouts = layouts(
+            r#"
+            [[layouts]]
+            name = "grid"
+            root = { columns = 2, grid = [
+                "temperature", "fan_speed",
+                "memory_used", "memory_free",
+                "power_usage",
+            ] }
+            "#,
+        );
+        let cells = layouts[0].cells(rect(0.0, 0.0, 200.0, 300.0));
+        let metric = Panel::Metric;
+        let expected = [
+            cell(metric(Metric::Temperature), rect(0.0, 200.0, 100.0, 300.0), Align::Left),
+            cell(metric(Metric::FanSpeed), rect(100.0, 200.0, 200.0, 300.0), Align::Right),
+            cell(metric(Metric::MemoryUsed), rect(0.0, 100.0, 100.0, 200.0), Align::Left),
+            cell(metric(Metric::MemoryFree), rect(100.0, 100.0, 200.0, 200.0), Align::Right),
+            // Alone in its row, so it takes the whole width and stays centred.
+            cell(metric(Metric::PowerUsage), rect(0.0, 0.0, 200.0, 100.0), Align::Center),
+        ];
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn unknown_panels_stay_empty() {
+        let layouts = layouts(
+            r#"
+            [[layouts]]
+            name = "typo"
+            root = { columns = 2, grid = ["temperatur", "clock"] }
+            "#,
+        );
+        let panels: Vec<Panel> = layouts[0]
+            .cells(rect(0.0, 0.0, 200.0, 100.0))
+            .iter()
+            .map(|cell| cell.panel)
+            .collect();
+        assert_eq!(panels, [Panel::Empty, Panel::Clock]);
+    }
+
+    #[test]
+    fn default_layouts() {
+        let layouts = Layout::from_config(&[]);
+        let names: Vec<&str> = layouts.iter().map(|layout| layout.name.as_str()).collect();
+        assert_eq!(names, ["standard", "clock_only"]);
+
+        let area = rect(0.0, 0.0, 720.0, 1080.0);
+        let standard = layouts[0].cells(area);
+        assert_eq!(standard.len(), 17);
+        assert_eq!((standard[0].panel, standard[1].panel), (Panel::Date, Panel::Clock));
+        let clock_only = layouts[1].cells(area);
+        let clock = Cell {
+            scale: 1.5,
+            ..cell(Panel::Clock, area, Align::Center)
+        };
+        assert_eq!(clock_only, [clock]);
+    }
+}
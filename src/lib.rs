@@ -0,0 +1,44 @@
+//! A clock with hardware info, drawn with conrod.
+//!
+//! The library holds everything except the window: the clock model, the metric providers, themes,
+//! widgets and the conrod side of the app. `gui::gui` draws the clock into a `Ui`, and
+//! `conrod_thread::run_conrod` runs it on its own thread, fed with `conrod_thread::Message`s by
+//! whoever owns the window. The `clock` binary is one such owner.
+
+extern crate chrono;
+#[macro_use]
+extern crate conrod_core;
+extern crate conrod_glium;
+extern crate find_folder;
+extern crate glium;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "nvml")]
+extern crate nvml_wrapper;
+extern crate serde;
+extern crate serde_json;
+#[cfg(test)]
+extern crate tempfile;
+extern crate toml;
+#[cfg(windows)]
+extern crate winapi;
+
+pub mod actions;
+pub mod config;
+pub mod conrod_thread;
+pub mod error;
+pub mod fonts;
+pub mod gesture;
+pub mod gui;
+pub mod layout;
+pub mod locale;
+pub mod metrics;
+pub mod my_widgets;
+pub mod night;
+pub mod theme;
+pub mod units;
+pub mod window;
+
+/// Default size of the window, and of the `Ui` until the first resize.
+pub const WIN_W: u32 = 720;
+pub const WIN_H: u32 = 1080;
@@ -0,0 +1,263 @@
+//! Translated labels, and dates with localized weekday and month names.
+//!
+//! The catalogs in `locales/` are built into the binary. The language comes from the `language`
+//! config setting, or else from `LC_ALL`, `LC_MESSAGES` or `LANG`. Anything a catalog does not
+//! translate falls back to English.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, TimeZone};
+use serde::Deserialize;
+
+use actions::Action;
+use gui::Mode;
+use metrics::{BatteryState, Metric};
+
+const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en.toml")),
+    ("nl", include_str!("../locales/nl.toml")),
+    ("de", include_str!("../locales/de.toml")),
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Catalog {
+    decimal_separator: Option<char>,
+    date_format: Option<String>,
+    /// Monday first.
+    weekdays: Vec<String>,
+    months: Vec<String>,
+    metrics: HashMap<String, String>,
+    actions: HashMap<String, String>,
+    modes: HashMap<String, String>,
+    menu: HashMap<String, String>,
+    throttle: HashMap<String, String>,
+    system: HashMap<String, String>,
+    battery: HashMap<String, String>,
+    statistics: HashMap<String, String>,
+}
+
+impl Catalog {
+    fn load(language: &str) -> Option<Catalog> {
+        let (_, text) = CATALOGS.iter().find(|&&(name, _)| name == language)?;
+        Some(toml::from_str(text).unwrap())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Locale {
+    pub language: String,
+    catalog: Catalog,
+    english: Catalog,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::new(Some("en"))
+    }
+}
+
+impl Locale {
+    /// The locale for `language`, or for the environment when it is `None`.
+    pub fn new(language: Option<&str>) -> Locale {
+        let language = match language {
+            Some(language) => language.to_string(),
+            None => from_env(),
+        };
+        let english = Catalog::load("en").unwrap();
+        let catalog = match Catalog::load(&language) {
+            Some(catalog) => catalog,
+            None => {
+                eprintln!("no translation for language {}, using English", language);
+                english.clone()
+            }
+        };
+        Locale {
+            language,
+            catalog,
+            english,
+        }
+    }
+
+    pub fn metric(&self, metric: Metric) -> &str {
+        self.lookup(|c| &c.metrics, metric.name())
+    }
+
+    pub fn action(&self, action: Action) -> &str {
+        self.lookup(|c| &c.actions, action.name())
+    }
+
+    pub fn mode(&self, mode: Mode) -> &str {
+        let key = match mode {
+            Mode::Clock => "clock",
+            Mode::Stopwatch => "stopwatch",
+            Mode::Timer => "timer",
+        };
+        self.lookup(|c| &c.modes, key)
+    }
+
+    /// A reason for clock throttling, one of `metrics::THROTTLE_REASONS` or `"none"`.
+    pub fn throttle<'a>(&'a self, reason: &'a str) -> &'a str {
+        self.lookup(|c| &c.throttle, reason)
+    }
+
+    /// A context menu entry, with `{name}` placeholders replaced by the matching `args`.
+    pub fn menu(&self, key: &str, args: &[(&str, &str)]) -> String {
+        fill(self.lookup(|c| &c.menu, key), args)
+    }
+
+    pub fn battery(&self, state: BatteryState) -> &str {
+        self.lookup(|c| &c.battery, state.name())
+    }
+
+    /// A line of the network, disk or filesystem panel, with `{name}` placeholders filled in.
+    pub fn system(&self, key: &str, args: &[(&str, &str)]) -> String {
+        fill(self.lookup(|c| &c.system, key), args)
+    }
+
+    /// The statistics under a metric, with `{min}`, `{max}`, `{mean}` and `{p95}` filled in.
+    pub fn statistics(&self, args: &[(&str, &str)]) -> String {
+        fill(self.lookup(|c| &c.statistics, "line"), args)
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        self.catalog
+            .decimal_separator
+            .or(self.english.decimal_separator)
+            .unwrap_or('.')
+    }
+
+    /// The date of `time` with the weekday and month written out.
+    pub fn date<Tz: TimeZone>(&self, time: &chrono::DateTime<Tz>) -> String {
+        let format = self
+            .catalog
+            .date_format
+            .as_ref()
+            .or_else(|| self.english.date_format.as_ref())
+            .map_or("{weekday} {day} {month} {year}", String::as_str);
+        let weekday = time.weekday().num_days_from_monday() as usize;
+        let month = time.month0() as usize;
+        let day = time.day().to_string();
+        let year = time.year().to_string();
+        fill(
+            format,
+            &[
+                ("weekday", self.name(|c| &c.weekdays, 7, weekday)),
+                ("day", day.as_str()),
+                ("month", self.name(|c| &c.months, 12, month)),
+                ("year", year.as_str()),
+            ],
+        )
+    }
+
+    fn lookup<'a, F>(&'a self, section: F, key: &'a str) -> &'a str
+    where
+        F: Fn(&Catalog) -> &HashMap<String, String>,
+    {
+        section(&self.catalog)
+            .get(key)
+            .or_else(|| section(&self.english).get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// Entry `index` of a list of `count` names, from English when the catalog's list is off.
+    fn name<F>(&self, list: F, count: usize, index: usize) -> &str
+    where
+        F: Fn(&Catalog) -> &Vec<String>,
+    {
+        let names = if list(&self.catalog).len() == count {
+            list(&self.catalog)
+        } else {
+            list(&self.english)
+        };
+        names.get(index).map_or("", String::as_str)
+    }
+}
+
+/// Replace every `{name}` in `template` with its value from `args`.
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(template.to_string(), |text, &(name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// The language part of the first locale variable that is set, like `nl` for `nl_NL.UTF-8`.
+fn from_env() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let language: String = value
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            match language.as_str() {
+                "" | "C" | "POSIX" => "en".to_string(),
+                _ => language.to_ascii_lowercase(),
+            }
+        })
+        .unwrap_or_else(|| "en".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics::THROTTLE_REASONS;
+
+    #[test]
+    fn catalogs_are_complete() {
+        let menu = [
+            "fullscreen",
+            "always_on_top",
+            "next_theme",
+            "next_layout",
+            "mode",
+            "start_stop",
+            "reset",
+            "gpu",
+            "quit",
+        ];
+        let states = [
+            BatteryState::Charging,
+            BatteryState::Discharging,
+            BatteryState::Full,
+            BatteryState::NotCharging,
+            BatteryState::Unknown,
+        ];
+        for &(language, text) in CATALOGS.iter() {
+            let catalog: Catalog = match toml::from_str(text) {
+                Ok(catalog) => catalog,
+                Err(e) => panic!("{}: {}", language, e),
+            };
+            let check = |section: &str, keys: &HashMap<String, String>, key: &str| {
+                assert!(keys.contains_key(key), "{}: [{}] {} is missing", language, section, key);
+            };
+            for metric in Metric::ALL {
+                check("metrics", &catalog.metrics, metric.name());
+            }
+            for action in Action::ALL.iter() {
+                check("actions", &catalog.actions, action.name());
+            }
+            for &mode in ["clock", "stopwatch", "timer"].iter() {
+                check("modes", &catalog.modes, mode);
+            }
+            for &key in menu.iter() {
+                check("menu", &catalog.menu, key);
+            }
+            for &reason in THROTTLE_REASONS.iter().chain(Some(&"none")) {
+                check("throttle", &catalog.throttle, reason);
+            }
+            for &key in ["network", "disk", "filesystem"].iter() {
+                check("system", &catalog.system, key);
+            }
+            for state in states.iter() {
+                check("battery", &catalog.battery, state.name());
+            }
+            check("statistics", &catalog.statistics, "line");
+            assert_eq!(catalog.weekdays.len(), 7, "{}: weekdays", language);
+            assert_eq!(catalog.months.len(), 12, "{}: months", language);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Battery charge and system power draw from `/sys/class/power_supply`.
+//!
+//! Laptops can have more than one battery; they are added up as if they were one. Drivers report
+//! either energy (µWh and µW) or charge (µAh and µA, with the voltage in µV), so both are read.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    Full,
+    NotCharging,
+    Unknown,
+}
+
+impl BatteryState {
+    fn from_status(status: &str) -> BatteryState {
+        match status {
+            "Charging" => BatteryState::Charging,
+            "Discharging" => BatteryState::Discharging,
+            "Full" => BatteryState::Full,
+            "Not charging" => BatteryState::NotCharging,
+            _ => BatteryState::Unknown,
+        }
+    }
+
+    /// The name used in the catalogs.
+    pub fn name(self) -> &'static str {
+        match self {
+            BatteryState::Charging => "charging",
+            BatteryState::Discharging => "discharging",
+            BatteryState::Full => "full",
+            BatteryState::NotCharging => "not_charging",
+            BatteryState::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryStatus {
+    pub percent: f64,
+    pub state: BatteryState,
+    /// Until empty while discharging, until full while charging.
+    pub time_left: Option<Duration>,
+    /// Power flowing out of (or into) the batteries, in watts.
+    pub power: Option<f64>,
+}
+
+/// One battery in microwatt hours and microwatts.
+#[derive(Debug, Default)]
+struct Reading {
+    capacity: Option<f64>,
+    energy_now: Option<f64>,
+    energy_full: Option<f64>,
+    power_now: Option<f64>,
+}
+
+/// The batteries in `sys/class/power_supply` below a root directory, which is `/` unless pointed
+/// at a copy.
+#[derive(Debug, Clone)]
+pub struct PowerSupplies {
+    root: PathBuf,
+}
+
+impl PowerSupplies {
+    pub fn new<P: Into<PathBuf>>(root: P) -> PowerSupplies {
+        PowerSupplies { root: root.into() }
+    }
+
+    /// The combined status of all batteries, `None` when there are none.
+    pub fn sample(&self) -> Option<BatteryStatus> {
+        let entries = std::fs::read_dir(self.root.join("sys/class/power_supply")).ok()?;
+        let mut supplies: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        supplies.sort();
+        let batteries: Vec<&PathBuf> = supplies
+            .iter()
+            .filter(|dir| read(&dir.join("type")).map_or(false, |t| t == "Battery"))
+            .filter(|dir| read(&dir.join("present")).map_or(true, |p| p == "1"))
+            .collect();
+        if batteries.is_empty() {
+            return None;
+        }
+
+        // One charging battery makes the whole set charging; otherwise the first known state.
+        let states: Vec<BatteryState> = batteries
+            .iter()
+            .map(|dir| BatteryState::from_status(&read(&dir.join("status")).unwrap_or_default()))
+            .collect();
+        let state = if states.contains(&BatteryState::Charging) {
+            BatteryState::Charging
+        } else if states.contains(&BatteryState::Discharging) {
+            BatteryState::Discharging
+        } else {
+            states[0]
+        };
+
+        // Batteries that don't report a value are left out of its sum.
+        let readings: Vec<Reading> = batteries.iter().map(|dir| reading(dir)).collect();
+        let sum = |field: fn(&Reading) -> Option<f64>| -> Option<f64> {
+            let values: Vec<f64> = readings.iter().filter_map(field).collect();
+            if values.is_empty() {
+                None
+            } else {
+                Some(values.iter().sum())
+            }
+        };
+        let energy_now = sum(|r| r.energy_now);
+        let energy_full = sum(|r| r.energy_full);
+        let power = sum(|r| r.power_now).filter(|&p| p > 0.0);
+
+        let percent = match (energy_now, energy_full) {
+            (Some(now), Some(full)) if full > 0.0 => now * 100.0 / full,
+            _ => {
+                let capacities: Vec<f64> = readings.iter().filter_map(|r| r.capacity).collect();
+                capacities.iter().sum::<f64>() / capacities.len().max(1) as f64
+            }
+        };
+        let hours = match (state, energy_now, energy_full, power) {
+            (BatteryState::Discharging, Some(now), _, Some(power)) => Some(now / power),
+            (BatteryState::Charging, Some(now), Some(full), Some(power)) => {
+                Some((full - now).max(0.0) / power)
+            }
+            _ => None,
+        };
+
+        Some(BatteryStatus {
+            percent: percent.min(100.0).max(0.0),
+            state,
+            time_left: hours.map(|hours| Duration::from_secs((hours * 3600.0) as u64)),
+            power: power.map(|microwatts| microwatts / 1_000_000.0),
+        })
+    }
+}
+
+fn reading(dir: &Path) -> Reading {
+    let number = |name: &str| read(&dir.join(name)).and_then(|s| s.parse::<f64>().ok());
+    let capacity = number("capacity");
+    match (number("energy_now"), number("charge_now")) {
+        (Some(energy_now), _) => Reading {
+            capacity,
+            energy_now: Some(energy_now),
+            energy_full: number("energy_full"),
+            power_now: number("power_now"),
+        },
+        (None, Some(charge_now)) => {
+            // µAh times V gives µWh, and µA times V gives µW.
+            let volts = number("voltage_now").map_or(0.0, |microvolts| microvolts / 1_000_000.0);
+            Reading {
+                capacity,
+                energy_now: Some(charge_now * volts),
+                energy_full: number("charge_full").map(|charge| charge * volts),
+                power_now: number("current_now").map(|current| current.abs() * volts),
+            }
+        }
+        (None, None) => Reading {
+            capacity,
+            ..Reading::default()
+        },
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics::write_fixture;
+
+    /// Write the files of `sys/class/power_supply/<name>`.
+    fn supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+        for &(file, contents) in files {
+            write_fixture(root, &format!("sys/class/power_supply/{}/{}", name, file), contents);
+        }
+    }
+
+    fn sample(root: &Path) -> Option<BatteryStatus> {
+        PowerSupplies::new(root).sample()
+    }
+
+    fn hours(hours: u64) -> Option<Duration> {
+        Some(Duration::from_secs(hours * 3600))
+    }
+
+    #[test]
+    fn energy() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Discharging\n"),
+                ("energy_now", "30000000\n"),
+                ("energy_full", "60000000\n"),
+                ("power_now", "15000000\n"),
+                ("capacity", "49\n"),
+            ],
+        );
+        let status = BatteryStatus {
+            percent: 50.0,
+            state: BatteryState::Discharging,
+            time_left: hours(2),
+            power: Some(15.0),
+        };
+        assert_eq!(sample(dir.path()), Some(status));
+    }
+
+    #[test]
+    fn charge() {
+        let dir = tempfile::tempdir().unwrap();
+        // 2 Ah of 4 Ah at 12 V, charging with 1 A.
+        supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Charging\n"),
+                ("charge_now", "2000000\n"),
+                ("charge_full", "4000000\n"),
+                ("voltage_now", "12000000\n"),
+                ("current_now", "1000000\n"),
+            ],
+        );
+        let status = BatteryStatus {
+            percent: 50.0,
+            state: BatteryState::Charging,
+            time_left: hours(2),
+            power: Some(12.0),
+        };
+        assert_eq!(sample(dir.path()), Some(status));
+    }
+
+    #[test]
+    fn two_batteries_add_up() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Discharging\n"),
+                ("energy_now", "10000000\n"),
+                ("energy_full", "20000000\n"),
+                ("power_now", "5000000\n"),
+            ],
+        );
+        supply(
+            dir.path(),
+            "BAT1",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Unknown\n"),
+                ("energy_now", "30000000\n"),
+                ("energy_full", "40000000\n"),
+                ("power_now", "0\n"),
+            ],
+        );
+        supply(dir.path(), "AC", &[("type", "Mains\n"), ("online", "0\n")]);
+        let status = sample(dir.path()).unwrap();
+        assert!((status.percent - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(status.state, BatteryState::Discharging);
+        assert_eq!(status.time_left, hours(8));
+        assert_eq!(status.power, Some(5.0));
+    }
+
+    #[test]
+    fn battery_without_power_is_left_out_of_the_sum() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Discharging\n"),
+                ("energy_now", "10000000\n"),
+                ("energy_full", "20000000\n"),
+            ],
+        );
+        supply(
+            dir.path(),
+            "BAT1",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Discharging\n"),
+                ("energy_now", "30000000\n"),
+                ("energy_full", "60000000\n"),
+                ("power_now", "10000000\n"),
+            ],
+        );
+        let status = BatteryStatus {
+            percent: 50.0,
+            state: BatteryState::Discharging,
+            time_left: hours(4),
+            power: Some(10.0),
+        };
+        assert_eq!(sample(dir.path()), Some(status));
+    }
+
+    #[test]
+    fn missing_battery_is_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery\n"),
+                ("status", "Full\n"),
+                ("present", "1\n"),
+                ("capacity", "100\n"),
+            ],
+        );
+        supply(
+            dir.path(),
+            "BAT1",
+            &[("type", "Battery\n"), ("status", "Discharging\n"), ("present", "0\n")],
+        );
+        let status = BatteryStatus {
+            percent: 100.0,
+            state: BatteryState::Full,
+            time_left: None,
+            power: None,
+        };
+        assert_eq!(sample(dir.path()), Some(status));
+    }
+
+    #[test]
+    fn no_battery() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(sample(dir.path()), None);
+        supply(dir.path(), "AC", &[("type", "Mains\n"), ("online", "1\n")]);
+        assert_eq!(sample(dir.path()), None);
+    }
+}
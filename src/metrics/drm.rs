@@ -0,0 +1,367 @@
+//! Graphics card metrics for AMD and Intel cards, read from `/sys/class/drm`.
+//!
+//! amdgpu reports load and video memory in the device directory and the current clocks as the
+//! starred line of `pp_dpm_sclk` and `pp_dpm_mclk`; i915 only has frequency files next to the
+//! card. Temperature, fan and power come from the card's hwmon directory when there is one.
+//! Whatever a driver does not report stays zero.
+
+use std::path::{Path, PathBuf};
+
+use error::Error;
+
+use super::{GpuSnapshot, MetricsSource};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Driver {
+    Amdgpu,
+    I915,
+}
+
+#[derive(Debug, Clone)]
+struct Card {
+    /// `sys/class/drm/cardN`.
+    dir: PathBuf,
+    driver: Driver,
+}
+
+impl Card {
+    fn device(&self) -> PathBuf {
+        self.dir.join("device")
+    }
+}
+
+/// The AMD and Intel cards in `sys/class/drm` below a root directory, which is `/` unless
+/// pointed at a copy.
+#[derive(Debug, Clone)]
+pub struct DrmSource {
+    cards: Vec<Card>,
+}
+
+impl DrmSource {
+    pub fn new() -> Result<DrmSource, Error> {
+        DrmSource::with_root("/")
+    }
+
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<DrmSource, Error> {
+        let drm = root.as_ref().join("sys/class/drm");
+        let entries = std::fs::read_dir(&drm)
+            .map_err(|e| Error::Metrics(format!("could not read {}: {}", drm.display(), e)))?;
+        let mut dirs: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        dirs.sort();
+        let cards: Vec<Card> = dirs
+            .into_iter()
+            .filter(|dir| is_card(dir))
+            .filter_map(|dir| {
+                let driver = match driver_name(&dir.join("device"))?.as_str() {
+                    "amdgpu" => Driver::Amdgpu,
+                    "i915" => Driver::I915,
+                    _ => return None,
+                };
+                Some(Card { dir, driver })
+            })
+            .collect();
+        if cards.is_empty() {
+            return Err(Error::Metrics("no AMD or Intel graphics card found".to_string()));
+        }
+        Ok(DrmSource { cards })
+    }
+}
+
+/// `cardN` itself, not one of its connectors like `card0-DP-1`.
+fn is_card(dir: &Path) -> bool {
+    dir.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.trim_start_matches("card").parse::<u32>().ok())
+        .is_some()
+}
+
+/// The name of the kernel driver bound to a device, from its `driver` link or `uevent`.
+fn driver_name(device: &Path) -> Option<String> {
+    let link = std::fs::read_link(device.join("driver")).ok();
+    if let Some(name) = link.as_ref().and_then(|l| l.file_name()).and_then(|n| n.to_str()) {
+        return Some(name.to_string());
+    }
+    read(&device.join("uevent"))?
+        .lines()
+        .find(|line| line.starts_with("DRIVER="))
+        .map(|line| line["DRIVER=".len()..].to_string())
+}
+
+impl MetricsSource for DrmSource {
+    fn devices(&self) -> Vec<String> {
+        self.cards
+            .iter()
+            .map(|card| {
+                let name = card.dir.file_name().map_or("", |n| n.to_str().unwrap_or(""));
+                match read(&card.device().join("product_name")) {
+                    Some(ref product) if !product.is_empty() => product.clone(),
+                    _ => match card.driver {
+                        Driver::Amdgpu => format!("AMD graphics ({})", name),
+                        Driver::I915 => format!("Intel graphics ({})", name),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        let card = self.cards.get(gpu as usize)?;
+        let mut snapshot = match card.driver {
+            Driver::Amdgpu => amdgpu(&card.device()),
+            Driver::I915 => i915(&card.dir),
+        };
+        if let Some(hwmon) = hwmon(&card.device()) {
+            let value = |name: &str| number(&hwmon.join(name));
+            // Millidegrees, microwatts and a fan duty cycle out of `pwm1_max`.
+            snapshot.temperature = value("temp1_input").map_or(0, |t| (t / 1000) as u32);
+            let power = value("power1_average").or_else(|| value("power1_input"));
+            snapshot.power_usage = power.map_or(0, |p| (p / 1000) as u32);
+            snapshot.power_limit = value("power1_cap").map_or(0, |p| (p / 1000) as u32);
+            if let Some(pwm) = value("pwm1") {
+                let max = value("pwm1_max").unwrap_or(255).max(1);
+                snapshot.fan_speed = (pwm * 100 / max) as u32;
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+fn amdgpu(device: &Path) -> GpuSnapshot {
+    let value = |name: &str| number(&device.join(name));
+    let memory_used = value("mem_info_vram_used").unwrap_or(0);
+    let memory_total = value("mem_info_vram_total").unwrap_or(0);
+    let (graphics_clock, max_graphics_clock) = dpm_clocks(&device.join("pp_dpm_sclk"));
+    let (memory_clock, max_memory_clock) = dpm_clocks(&device.join("pp_dpm_mclk"));
+    GpuSnapshot {
+        graphics_clock,
+        max_graphics_clock,
+        memory_clock,
+        max_memory_clock,
+        memory_used,
+        memory_total,
+        memory_free: memory_total.saturating_sub(memory_used),
+        gpu_utilization: value("gpu_busy_percent").unwrap_or(0) as u32,
+        memory_utilization: value("mem_busy_percent").unwrap_or(0) as u32,
+        pcie_generation: read(&device.join("current_link_speed")).map_or(0, |s| pcie_gen(&s)),
+        pcie_width: value("current_link_width").unwrap_or(0) as u32,
+        ..GpuSnapshot::default()
+    }
+}
+
+fn i915(card: &Path) -> GpuSnapshot {
+    let mhz = |name: &str| number(&card.join(name)).unwrap_or(0) as u32;
+    // The actual frequency, when the kernel has it, is what the card runs at right now.
+    let graphics_clock = match mhz("gt_act_freq_mhz") {
+        0 => mhz("gt_cur_freq_mhz"),
+        actual => actual,
+    };
+    GpuSnapshot {
+        graphics_clock,
+        max_graphics_clock: mhz("gt_max_freq_mhz"),
+        ..GpuSnapshot::default()
+    }
+}
+
+/// Current and highest clock in MHz from a `pp_dpm_*` file, which lists levels like
+/// `1: 1000Mhz *` with the active one starred.
+fn dpm_clocks(path: &Path) -> (u32, u32) {
+    let text = read(path).unwrap_or_default();
+    let levels: Vec<(u32, bool)> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let mhz = fields.next()?.to_lowercase();
+            let mhz = mhz.trim_end_matches("mhz").parse().ok()?;
+            Some((mhz, fields.next() == Some("*")))
+        })
+        .collect();
+    let current = levels.iter().find(|&&(_, active)| active).map_or(0, |&(mhz, _)| mhz);
+    let max = levels.iter().map(|&(mhz, _)| mhz).max().unwrap_or(0);
+    (current, max)
+}
+
+/// The PCIe generation for a `current_link_speed` like `8.0 GT/s PCIe`.
+fn pcie_gen(speed: &str) -> u32 {
+    let rate: f64 = speed
+        .split_whitespace()
+        .next()
+        .and_then(|rate| rate.parse().ok())
+        .unwrap_or(0.0);
+    match rate {
+        r if r >= 64.0 => 6,
+        r if r >= 32.0 => 5,
+        r if r >= 16.0 => 4,
+        r if r >= 8.0 => 3,
+        r if r >= 5.0 => 2,
+        r if r > 0.0 => 1,
+        _ => 0,
+    }
+}
+
+/// The first `hwmon/hwmonN` directory of a device.
+fn hwmon(device: &Path) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(device.join("hwmon"))
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    dirs.sort();
+    dirs.into_iter().next()
+}
+
+fn number(path: &Path) -> Option<u64> {
+    read(path)?.parse().ok()
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics::write_fixture;
+
+    /// An AMD card0 with a connector and a render node, and an Intel card1.
+    fn tree(root: &Path) {
+        let amd = "sys/class/drm/card0/device";
+        let files = [
+            ("uevent", "DRIVER=amdgpu\nPCI_ID=1002:73BF\n"),
+            ("product_name", "Radeon RX 6800\n"),
+            ("mem_info_vram_used", "2147483648\n"),
+            ("mem_info_vram_total", "17179869184\n"),
+            ("gpu_busy_percent", "37\n"),
+            ("mem_busy_percent", "12\n"),
+            ("current_link_speed", "16.0 GT/s PCIe\n"),
+            ("current_link_width", "16\n"),
+            ("pp_dpm_sclk", "0: 500Mhz\n1: 1815Mhz *\n2: 2475Mhz\n"),
+            ("pp_dpm_mclk", "0: 96Mhz\n1: 1000Mhz *\n"),
+            ("hwmon/hwmon3/temp1_input", "54000\n"),
+            ("hwmon/hwmon3/power1_average", "123456789\n"),
+            ("hwmon/hwmon3/power1_cap", "250000000\n"),
+            ("hwmon/hwmon3/pwm1", "128\n"),
+            ("hwmon/hwmon3/pwm1_max", "255\n"),
+        ];
+        for &(file, contents) in files.iter() {
+            write_fixture(root, &format!("{}/{}", amd, file), contents);
+        }
+        // Connectors and render nodes belong to a card but are not one.
+        write_fixture(root, "sys/class/drm/card0-DP-1/device/uevent", "DRIVER=amdgpu\n");
+        write_fixture(root, "sys/class/drm/renderD128/device/uevent", "DRIVER=amdgpu\n");
+        write_fixture(root, "sys/class/drm/version", "drm 1.1.0 20060810\n");
+
+        write_fixture(root, "sys/class/drm/card1/device/uevent", "DRIVER=i915\n");
+        write_fixture(root, "sys/class/drm/card1/gt_act_freq_mhz", "0\n");
+        write_fixture(root, "sys/class/drm/card1/gt_cur_freq_mhz", "1100\n");
+        write_fixture(root, "sys/class/drm/card1/gt_max_freq_mhz", "1450\n");
+    }
+
+    #[test]
+    fn finds_cards_but_not_connectors() {
+        let dir = tempfile::tempdir().unwrap();
+        tree(dir.path());
+        let source = DrmSource::with_root(dir.path()).unwrap();
+        assert_eq!(source.devices(), ["Radeon RX 6800", "Intel graphics (card1)"]);
+    }
+
+    #[test]
+    fn amdgpu_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        tree(dir.path());
+        let mut source = DrmSource::with_root(dir.path()).unwrap();
+        let expected = GpuSnapshot {
+            graphics_clock: 1815,
+            max_graphics_clock: 2475,
+            memory_clock: 1000,
+            max_memory_clock: 1000,
+            memory_used: 2 << 30,
+            memory_total: 16 << 30,
+            memory_free: 14 << 30,
+            gpu_utilization: 37,
+            memory_utilization: 12,
+            pcie_generation: 4,
+            pcie_width: 16,
+            temperature: 54,
+            power_usage: 123_456,
+            power_limit: 250_000,
+            fan_speed: 50,
+            ..GpuSnapshot::default()
+        };
+        assert_eq!(source.sample_gpu(0), Some(expected));
+    }
+
+    #[test]
+    fn i915_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        tree(dir.path());
+        let mut source = DrmSource::with_root(dir.path()).unwrap();
+        let expected = GpuSnapshot {
+            graphics_clock: 1100,
+            max_graphics_clock: 1450,
+            ..GpuSnapshot::default()
+        };
+        assert_eq!(source.sample_gpu(1), Some(expected));
+        assert_eq!(source.sample_gpu(2), None);
+
+        write_fixture(dir.path(), "sys/class/drm/card1/gt_act_freq_mhz", "900\n");
+        assert_eq!(source.sample_gpu(1).map(|gpu| gpu.graphics_clock), Some(900));
+    }
+
+    #[test]
+    fn no_supported_card() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(DrmSource::with_root(dir.path()).is_err());
+        write_fixture(dir.path(), "sys/class/drm/card0/device/uevent", "DRIVER=nvidia\n");
+        write_fixture(dir.path(), "sys/class/drm/card1-DP-1/device/uevent", "DRIVER=i915\n");
+        assert!(DrmSource::with_root(dir.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn driver_from_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let device = dir.path().join("device");
+        std::fs::create_dir(&device).unwrap();
+        let target = "../../../bus/pci/drivers/amdgpu";
+        std::os::unix::fs::symlink(target, device.join("driver")).unwrap();
+        assert_eq!(driver_name(&device).as_ref().map(String::as_str), Some("amdgpu"));
+    }
+
+    #[test]
+    fn dpm_levels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pp_dpm_sclk");
+        std::fs::write(&path, "0: 300MHz\n1: 800MHz\n2: 1300MHz *\n").unwrap();
+        assert_eq!(dpm_clocks(&path), (1300, 1300));
+        std::fs::write(&path, "0: 300Mhz *\n1: 800Mhz\n").unwrap();
+        assert_eq!(dpm_clocks(&path), (300, 800));
+        assert_eq!(dpm_clocks(&dir.path().join("pp_dpm_mclk")), (0, 0));
+    }
+
+    #[test]
+    fn pcie_generations() {
+        assert_eq!(pcie_gen("2.5 GT/s PCIe"), 1);
+        assert_eq!(pcie_gen("5.0 GT/s PCIe"), 2);
+        assert_eq!(pcie_gen("8.0 GT/s PCIe"), 3);
+        assert_eq!(pcie_gen("16.0 GT/s PCIe"), 4);
+        assert_eq!(pcie_gen("32.0 GT/s PCIe"), 5);
+        assert_eq!(pcie_gen("64.0 GT/s PCIe"), 6);
+        assert_eq!(pcie_gen("Unknown"), 0);
+    }
+
+    #[test]
+    fn hwmon_without_pwm_max() {
+        let dir = tempfile::tempdir().unwrap();
+        tree(dir.path());
+        let hwmon = dir.path().join("sys/class/drm/card0/device/hwmon/hwmon3");
+        std::fs::remove_file(hwmon.join("pwm1_max")).unwrap();
+        std::fs::remove_file(hwmon.join("power1_average")).unwrap();
+        write_fixture(&hwmon, "power1_input", "80000000\n");
+        write_fixture(&hwmon, "pwm1", "255\n");
+        let gpu = DrmSource::with_root(dir.path()).unwrap().sample_gpu(0).unwrap();
+        assert_eq!((gpu.fan_speed, gpu.power_usage), (100, 80_000));
+    }
+}
@@ -0,0 +1,276 @@
+//! Running statistics of the metrics: lowest, highest, mean and 95th percentile.
+//!
+//! Statistics cover everything since startup (or the last reset), or only the last few minutes
+//! when `[statistics] minutes` is set. The percentile needs the values themselves, so they are
+//! kept for the whole window, or for the last `HISTORY_LIMIT` samples without one; without a
+//! window the other figures are kept as running totals and cover the whole time. The kept values
+//! are also held in order, so the statistics of each sample need no sorting. Switching cards
+//! starts over.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use config::StatisticsConfig;
+use locale::Locale;
+use units::{Quantity, UnitFormat};
+
+use super::{Metric, Snapshot};
+
+/// Samples kept per metric without a window, a few hours at the default interval.
+const HISTORY_LIMIT: usize = 10_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    pub min: Quantity,
+    pub max: Quantity,
+    pub mean: Quantity,
+    pub p95: Quantity,
+}
+
+impl Stats {
+    /// The statistics as one line, like `min 40 °C  max 81 °C  mean 63 °C  p95 78 °C`.
+    pub fn line(&self, locale: &Locale, units: &UnitFormat) -> String {
+        let min = units.format(self.min);
+        let max = units.format(self.max);
+        let mean = units.format(self.mean);
+        let p95 = units.format(self.p95);
+        locale.statistics(&[
+            ("min", min.as_str()),
+            ("max", max.as_str()),
+            ("mean", mean.as_str()),
+            ("p95", p95.as_str()),
+        ])
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Series {
+    /// The last value, which gives the kind of quantity.
+    last: Quantity,
+    samples: VecDeque<(Instant, f64)>,
+    /// The values of `samples`, smallest first.
+    sorted: Vec<f64>,
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    /// The statistics as of the last sample.
+    stats: Option<Stats>,
+}
+
+impl Series {
+    fn new(quantity: Quantity) -> Series {
+        Series {
+            last: quantity,
+            samples: VecDeque::new(),
+            sorted: Vec::new(),
+            min: std::f64::INFINITY,
+            max: std::f64::NEG_INFINITY,
+            sum: 0.0,
+            count: 0,
+            stats: None,
+        }
+    }
+
+    fn push(&mut self, at: Instant, quantity: Quantity, window: Option<Duration>, limit: usize) {
+        let value = quantity.value();
+        self.last = quantity;
+        self.samples.push_back((at, value));
+        let index = position(&self.sorted, value).unwrap_or_else(|index| index);
+        self.sorted.insert(index, value);
+        if self.samples.len() > limit {
+            self.pop_front();
+        }
+        if let Some(window) = window {
+            while self.samples.front().map_or(false, |&(t, _)| at - t > window) {
+                self.pop_front();
+            }
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+        self.stats = self.compute(window);
+    }
+
+    fn pop_front(&mut self) {
+        if let Some((_, value)) = self.samples.pop_front() {
+            if let Ok(index) = position(&self.sorted, value) {
+                self.sorted.remove(index);
+            }
+        }
+    }
+
+    fn compute(&self, window: Option<Duration>) -> Option<Stats> {
+        let values = &self.sorted;
+        if values.is_empty() {
+            return None;
+        }
+        // Nearest rank: the smallest value with at least 95% of the values at or below it.
+        let rank = (values.len() as f64 * 0.95).ceil() as usize;
+        let p95 = values[rank.max(1) - 1];
+        let (min, max, mean) = match window {
+            Some(_) => {
+                let sum: f64 = values.iter().sum();
+                (values[0], values[values.len() - 1], sum / values.len() as f64)
+            }
+            None => (self.min, self.max, self.sum / self.count as f64),
+        };
+        let quantity = |value| self.last.with_value(value);
+        Some(Stats {
+            min: quantity(min),
+            max: quantity(max),
+            mean: quantity(mean),
+            p95: quantity(p95),
+        })
+    }
+}
+
+/// Where `value` is in `sorted`, or where it would go.
+fn position(sorted: &[f64], value: f64) -> Result<usize, usize> {
+    sorted.binary_search_by(|probe| probe.partial_cmp(&value).unwrap_or(Ordering::Equal))
+}
+
+/// Recent values of every metric that is a number, fed with each `Snapshot`.
+#[derive(Debug, Clone)]
+pub struct History {
+    window: Option<Duration>,
+    /// Samples kept per metric.
+    limit: usize,
+    gpu: u32,
+    series: HashMap<Metric, Series>,
+}
+
+impl History {
+    /// Statistics as configured, of samples taken every `interval`.
+    pub fn new(config: &StatisticsConfig, interval: Duration) -> History {
+        let window = match config.minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(u64::from(minutes) * 60)),
+        };
+        // Room for every sample in the window, counting the one at each end.
+        let interval_ms = (interval.as_millis() as u64).max(1);
+        let limit = window.map_or(HISTORY_LIMIT, |window| {
+            (window.as_millis() as u64 / interval_ms) as usize + 1
+        });
+        History {
+            window,
+            limit,
+            gpu: 0,
+            series: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, snapshot: &Snapshot) {
+        if snapshot.gpu_index != self.gpu {
+            self.reset();
+            self.gpu = snapshot.gpu_index;
+        }
+        let now = Instant::now();
+        for &metric in Metric::ALL {
+            if let Some(quantity) = metric.measure(snapshot) {
+                self.series
+                    .entry(metric)
+                    .or_insert_with(|| Series::new(quantity))
+                    .push(now, quantity, self.window, self.limit);
+            }
+        }
+    }
+
+    /// Forget everything measured so far.
+    pub fn reset(&mut self) {
+        self.series.clear();
+    }
+
+    /// Statistics of `metric`, `None` before it has a value or when it is not a number.
+    pub fn stats(&self, metric: Metric) -> Option<Stats> {
+        self.series.get(&metric)?.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics::{BatteryState, BatteryStatus, GpuSnapshot};
+
+    fn snapshot(temperature: u32, watts: f64) -> Snapshot {
+        let mut snapshot = Snapshot {
+            gpu: Some(GpuSnapshot {
+                temperature,
+                ..GpuSnapshot::default()
+            }),
+            ..Snapshot::default()
+        };
+        snapshot.system.battery = Some(BatteryStatus {
+            percent: 80.0,
+            state: BatteryState::Discharging,
+            time_left: None,
+            power: Some(watts),
+        });
+        snapshot
+    }
+
+    fn config(minutes: u32) -> StatisticsConfig {
+        StatisticsConfig {
+            show: true,
+            minutes,
+        }
+    }
+
+    #[test]
+    fn statistics_of_every_sample() {
+        let mut history = History::new(&config(0), Duration::from_secs(1));
+        for value in (1..=100).rev() {
+            history.push(&snapshot(value, value as f64));
+        }
+        let temperature = Stats {
+            min: Quantity::Celsius(1.0),
+            max: Quantity::Celsius(100.0),
+            mean: Quantity::Celsius(50.5),
+            p95: Quantity::Celsius(95.0),
+        };
+        assert_eq!(history.stats(Metric::Temperature), Some(temperature));
+        let power = history.stats(Metric::SystemPower).unwrap();
+        assert_eq!((power.min, power.max), (Quantity::Watts(1.0), Quantity::Watts(100.0)));
+        assert_eq!(history.stats(Metric::PerformanceState), None);
+
+        history.reset();
+        assert_eq!(history.stats(Metric::Temperature), None);
+    }
+
+    #[test]
+    fn oldest_samples_leave_the_percentile() {
+        let mut history = History::new(&config(0), Duration::from_secs(1));
+        for value in 1..=HISTORY_LIMIT as u32 + 100 {
+            history.push(&snapshot(value % 200, 10.0));
+        }
+        let series = &history.series[&Metric::Temperature];
+        assert_eq!(series.sorted.len(), HISTORY_LIMIT);
+        assert!(series.sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+        let mut kept: Vec<f64> = series.samples.iter().map(|&(_, value)| value).collect();
+        kept.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(kept, series.sorted);
+    }
+
+    #[test]
+    fn switching_cards_starts_over() {
+        let mut history = History::new(&config(0), Duration::from_secs(1));
+        history.push(&snapshot(90, 10.0));
+        let mut other = snapshot(40, 10.0);
+        other.gpu_index = 1;
+        history.push(&other);
+        let max = history.stats(Metric::Temperature).map(|stats| stats.max);
+        assert_eq!(max, Some(Quantity::Celsius(40.0)));
+    }
+
+    #[test]
+    fn buffer_holds_the_window() {
+        let second = Duration::from_secs(1);
+        assert_eq!(History::new(&config(0), second).limit, HISTORY_LIMIT);
+        assert_eq!(History::new(&config(15), second).limit, 901);
+        assert_eq!(History::new(&config(15), Duration::from_millis(200)).limit, 4501);
+        assert_eq!(History::new(&config(1), Duration::from_millis(0)).limit, 60_001);
+    }
+}
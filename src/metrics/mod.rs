@@ -0,0 +1,561 @@
+//! Hardware metrics, sampled on their own thread.
+//!
+//! A `MetricsSource` knows how to read the graphics card and a `SystemSource` the network, disks
+//! and filesystems; `run_sampler` calls both at a fixed interval and sends each `Snapshot` to the
+//! conrod thread, so slow driver calls never hold up drawing.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use actions::Action;
+use conrod_thread::Message;
+use locale::Locale;
+use units::{Quantity, UnitFormat};
+
+mod battery;
+mod drm;
+mod history;
+#[cfg(feature = "nvml")]
+mod nvml;
+mod process;
+mod replay;
+mod system;
+
+pub use self::battery::{BatteryState, BatteryStatus, PowerSupplies};
+pub use self::drm::DrmSource;
+pub use self::history::{History, Stats};
+#[cfg(feature = "nvml")]
+pub use self::nvml::NvmlSource;
+pub use self::process::{GpuProcess, ProcessTable};
+pub use self::replay::{Recorder, ReplaySource};
+pub use self::system::{DiskRates, FilesystemUsage, InterfaceRates, SystemSnapshot, SystemSource};
+
+/// One sample of a graphics card. Memory is in bytes, clocks in MHz and power in milliwatts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpuSnapshot {
+    pub memory_clock: u32,
+    pub graphics_clock: u32,
+    pub video_clock: u32,
+    pub max_memory_clock: u32,
+    pub max_graphics_clock: u32,
+    pub temperature: u32,
+    pub fan_speed: u32,
+    pub memory_used: u64,
+    pub memory_free: u64,
+    pub memory_total: u64,
+    pub memory_utilization: u32,
+    pub gpu_utilization: u32,
+    pub encoder_utilization: u32,
+    pub decoder_utilization: u32,
+    pub power_usage: u32,
+    pub power_limit: u32,
+    /// The `n` of performance state `Pn`, from 0 for full speed to 15 for idle.
+    pub performance_state: Option<u32>,
+    /// Why the clocks are held back, as `THROTTLE_REASONS` names.
+    pub throttle_reasons: Vec<String>,
+    pub pcie_generation: u32,
+    pub pcie_width: u32,
+    /// PCIe traffic in bytes per second.
+    pub pcie_tx: u64,
+    pub pcie_rx: u64,
+    /// Uncorrected ECC errors since the driver loaded, `None` without ECC memory.
+    pub ecc_errors: Option<u64>,
+}
+
+/// Names of the reasons a card can be throttled, as used in `GpuSnapshot::throttle_reasons`.
+pub const THROTTLE_REASONS: [&str; 7] = [
+    "idle",
+    "application_clocks",
+    "power_cap",
+    "hw_slowdown",
+    "sync_boost",
+    "sw_thermal",
+    "hw_thermal",
+];
+
+/// Versions of the driver stack, the same for every card.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DriverInfo {
+    pub driver_version: String,
+    pub cuda_version: String,
+}
+
+macro_rules! metrics {
+    ($($metric:ident => $name:expr,)*) => {
+        /// A single value of a `Snapshot`, as placed by a layout.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Metric {
+            $($metric,)*
+        }
+
+        impl Metric {
+            pub const ALL: &'static [Metric] = &[$(Metric::$metric,)*];
+
+            /// The name used for the metric in layouts and catalogs.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Metric::$metric => $name,)*
+                }
+            }
+        }
+    };
+}
+
+metrics! {
+    MemoryClock => "memory_clock",
+    GraphicsClock => "graphics_clock",
+    VideoClock => "video_clock",
+    MaxMemoryClock => "max_memory_clock",
+    MaxGraphicsClock => "max_graphics_clock",
+    Temperature => "temperature",
+    FanSpeed => "fan_speed",
+    MemoryUsed => "memory_used",
+    MemoryFree => "memory_free",
+    MemoryTotal => "memory_total",
+    MemoryUtilization => "memory_utilization",
+    GpuUtilization => "gpu_utilization",
+    EncoderUtilization => "encoder_utilization",
+    DecoderUtilization => "decoder_utilization",
+    PowerUsage => "power_usage",
+    PowerLimit => "power_limit",
+    PerformanceState => "performance_state",
+    ThrottleReasons => "throttle_reasons",
+    PcieLink => "pcie_link",
+    PcieTx => "pcie_tx",
+    PcieRx => "pcie_rx",
+    EccErrors => "ecc_errors",
+    DriverVersion => "driver_version",
+    CudaVersion => "cuda_version",
+    Battery => "battery",
+    BatteryState => "battery_state",
+    BatteryTime => "battery_time",
+    SystemPower => "system_power",
+}
+
+impl Metric {
+    pub fn from_name(name: &str) -> Option<Metric> {
+        Metric::ALL.iter().cloned().find(|m| m.name() == name)
+    }
+
+    /// The measured value, `None` for metrics that are not a number with a unit.
+    pub fn quantity(self, gpu: &GpuSnapshot) -> Option<Quantity> {
+        const MHZ: f64 = 1_000_000.0;
+        let mhz = |clock: u32| Some(Quantity::Hertz(clock as f64 * MHZ));
+        let percent = |value: u32| Some(Quantity::Percent(value as f64));
+        let watts = |milliwatts: u32| Some(Quantity::Watts(milliwatts as f64 / 1000.0));
+        match self {
+            Metric::MemoryClock => mhz(gpu.memory_clock),
+            Metric::GraphicsClock => mhz(gpu.graphics_clock),
+            Metric::VideoClock => mhz(gpu.video_clock),
+            Metric::MaxMemoryClock => mhz(gpu.max_memory_clock),
+            Metric::MaxGraphicsClock => mhz(gpu.max_graphics_clock),
+            Metric::Temperature => Some(Quantity::Celsius(gpu.temperature as f64)),
+            Metric::FanSpeed => percent(gpu.fan_speed),
+            Metric::MemoryUsed => Some(Quantity::Bytes(gpu.memory_used)),
+            Metric::MemoryFree => Some(Quantity::Bytes(gpu.memory_free)),
+            Metric::MemoryTotal => Some(Quantity::Bytes(gpu.memory_total)),
+            Metric::MemoryUtilization => percent(gpu.memory_utilization),
+            Metric::GpuUtilization => percent(gpu.gpu_utilization),
+            Metric::EncoderUtilization => percent(gpu.encoder_utilization),
+            Metric::DecoderUtilization => percent(gpu.decoder_utilization),
+            Metric::PowerUsage => watts(gpu.power_usage),
+            Metric::PowerLimit => watts(gpu.power_limit),
+            Metric::PcieTx => Some(Quantity::BytesPerSecond(gpu.pcie_tx)),
+            Metric::PcieRx => Some(Quantity::BytesPerSecond(gpu.pcie_rx)),
+            _ => None,
+        }
+    }
+
+    /// Like `quantity`, for any metric of a snapshot, including the battery's.
+    pub fn measure(self, snapshot: &Snapshot) -> Option<Quantity> {
+        match self {
+            Metric::Battery => Some(Quantity::Percent(snapshot.system.battery.as_ref()?.percent)),
+            Metric::SystemPower => Some(Quantity::Watts(snapshot.system.battery.as_ref()?.power?)),
+            _ => self.quantity(snapshot.gpu.as_ref()?),
+        }
+    }
+
+    /// `label: value`, like `Memory used: 1.2 GiB`; `None` when there is no value to show.
+    pub fn line(self, snapshot: &Snapshot, locale: &Locale, units: &UnitFormat) -> Option<String> {
+        let value = match self {
+            Metric::DriverVersion => snapshot.driver.driver_version.clone(),
+            Metric::CudaVersion => snapshot.driver.cuda_version.clone(),
+            Metric::Battery | Metric::BatteryState | Metric::BatteryTime | Metric::SystemPower => {
+                let battery = snapshot.system.battery.as_ref()?;
+                battery_value(self, battery, locale, units)?
+            }
+            _ => {
+                let gpu = snapshot.gpu.as_ref()?;
+                match self.quantity(gpu) {
+                    Some(quantity) => units.format(quantity),
+                    None => self.text(gpu, locale),
+                }
+            }
+        };
+        Some(format!("{}: {}", locale.metric(self), value))
+    }
+
+    fn text(self, gpu: &GpuSnapshot, locale: &Locale) -> String {
+        match self {
+            Metric::PerformanceState => match gpu.performance_state {
+                Some(state) => format!("P{}", state),
+                None => "-".to_string(),
+            },
+            Metric::ThrottleReasons if gpu.throttle_reasons.is_empty() => {
+                locale.throttle("none").to_string()
+            }
+            Metric::ThrottleReasons => gpu
+                .throttle_reasons
+                .iter()
+                .map(|reason| locale.throttle(reason))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Metric::PcieLink => format!("Gen {} x{}", gpu.pcie_generation, gpu.pcie_width),
+            Metric::EccErrors => match gpu.ecc_errors {
+                Some(errors) => errors.to_string(),
+                None => "-".to_string(),
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+/// A battery metric's value, `None` for times and power the driver does not report.
+fn battery_value(
+    metric: Metric,
+    battery: &BatteryStatus,
+    locale: &Locale,
+    units: &UnitFormat,
+) -> Option<String> {
+    match metric {
+        Metric::Battery => Some(units.format(Quantity::Percent(battery.percent))),
+        Metric::BatteryState => Some(locale.battery(battery.state).to_string()),
+        Metric::BatteryTime => {
+            let minutes = battery.time_left?.as_secs() / 60;
+            Some(format!("{}:{:02}", minutes / 60, minutes % 60))
+        }
+        Metric::SystemPower => Some(units.format(Quantity::Watts(battery.power?))),
+        _ => None,
+    }
+}
+
+/// Everything sampled at one moment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Index of the sampled graphics card.
+    pub gpu_index: u32,
+    pub gpu: Option<GpuSnapshot>,
+    pub driver: DriverInfo,
+    /// Processes using the card, the biggest users of graphics memory first.
+    pub processes: Vec<GpuProcess>,
+    pub system: SystemSnapshot,
+}
+
+pub trait MetricsSource {
+    /// Names of the graphics cards that can be sampled.
+    fn devices(&self) -> Vec<String>;
+
+    /// Called before each sample, so everything read for it belongs to the same moment.
+    fn tick(&mut self) {}
+
+    /// Sample the graphics card with index `gpu`, `None` if it cannot be read.
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot>;
+
+    /// Processes using the graphics card with index `gpu`.
+    fn processes(&mut self, _gpu: u32) -> Vec<GpuProcess> {
+        Vec::new()
+    }
+
+    /// Driver and CUDA versions, read once at startup.
+    fn driver(&self) -> DriverInfo {
+        DriverInfo::default()
+    }
+
+    /// Network, disk, filesystem and battery figures, read from `system` unless the source has
+    /// its own, like a recording does.
+    fn sample_system(&mut self, system: &mut SystemSource) -> SystemSnapshot {
+        system.sample()
+    }
+}
+
+impl<S: MetricsSource + ?Sized> MetricsSource for Box<S> {
+    fn devices(&self) -> Vec<String> {
+        (**self).devices()
+    }
+
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        (**self).sample_gpu(gpu)
+    }
+
+    fn processes(&mut self, gpu: u32) -> Vec<GpuProcess> {
+        (**self).processes(gpu)
+    }
+
+    fn driver(&self) -> DriverInfo {
+        (**self).driver()
+    }
+
+    fn sample_system(&mut self, system: &mut SystemSource) -> SystemSnapshot {
+        (**self).sample_system(system)
+    }
+}
+
+/// The first source that finds a graphics card: NVML for NVIDIA cards when built with the `nvml`
+/// feature, then sysfs for AMD and Intel cards, and `NoGpu` when neither does.
+pub fn detect() -> Box<dyn MetricsSource> {
+    #[cfg(feature = "nvml")]
+    match NvmlSource::new() {
+        // NVML also loads on machines whose NVIDIA card is gone or unsupported.
+        Ok(ref source) if source.devices().is_empty() => eprintln!("no NVIDIA graphics card found"),
+        Ok(source) => return Box::new(source),
+        Err(e) => eprintln!("{}", e),
+    }
+    match DrmSource::new() {
+        Ok(source) => Box::new(source),
+        Err(e) => {
+            eprintln!("{}", e);
+            Box::new(NoGpu)
+        }
+    }
+}
+
+/// A source for machines without a supported graphics card, so the system panels still work.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoGpu;
+
+impl MetricsSource for NoGpu {
+    fn devices(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn sample_gpu(&mut self, _gpu: u32) -> Option<GpuSnapshot> {
+        None
+    }
+}
+
+/// Write `contents` to `path` below `root`, creating the directories, for fake `/proc` and `/sys`
+/// trees in tests.
+#[cfg(test)]
+fn write_fixture(root: &std::path::Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+/// Cards that report their index as the temperature, for tests of the sampler and recordings.
+#[cfg(test)]
+struct MockSource {
+    devices: usize,
+}
+
+#[cfg(test)]
+impl MetricsSource for MockSource {
+    fn devices(&self) -> Vec<String> {
+        (0..self.devices).map(|i| format!("Mock GPU {}", i)).collect()
+    }
+
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        Some(GpuSnapshot {
+            temperature: gpu,
+            ..GpuSnapshot::default()
+        })
+    }
+
+    fn processes(&mut self, gpu: u32) -> Vec<GpuProcess> {
+        vec![GpuProcess {
+            pid: 100 + gpu,
+            ..GpuProcess::default()
+        }]
+    }
+
+    fn driver(&self) -> DriverInfo {
+        DriverInfo {
+            driver_version: "1.2.3".to_string(),
+            cuda_version: String::new(),
+        }
+    }
+
+    /// A half full battery instead of the figures of the machine running the tests.
+    fn sample_system(&mut self, _system: &mut SystemSource) -> SystemSnapshot {
+        SystemSnapshot {
+            battery: Some(BatteryStatus {
+                percent: 50.0,
+                state: BatteryState::Discharging,
+                time_left: None,
+                power: Some(10.0),
+            }),
+            ..SystemSnapshot::default()
+        }
+    }
+}
+
+/// Sample `source` and `system` every `interval` until the conrod thread hangs up.
+///
+/// `commands` receives the `CycleGpu` and `SelectGpu` actions; a new snapshot is taken right away
+/// after switching cards.
+pub fn run_sampler<S: MetricsSource>(
+    mut source: S,
+    mut system: SystemSource,
+    interval: Duration,
+    commands: Receiver<Action>,
+    snapshots: Sender<Message>,
+) {
+    let devices = source.devices();
+    let driver = source.driver();
+    let gpu_count = devices.len().max(1) as u32;
+    if snapshots.send(Message::Devices(devices)).is_err() {
+        return;
+    }
+
+    let mut gpu_index = 0;
+    loop {
+        source.tick();
+        let snapshot = Snapshot {
+            gpu_index,
+            gpu: source.sample_gpu(gpu_index),
+            driver: driver.clone(),
+            processes: source.processes(gpu_index),
+            system: source.sample_system(&mut system),
+        };
+        if snapshots.send(Message::Metrics(snapshot)).is_err() {
+            return;
+        }
+
+        match commands.recv_timeout(interval) {
+            Ok(Action::CycleGpu) => gpu_index = (gpu_index + 1) % gpu_count,
+            Ok(Action::SelectGpu(gpu)) if gpu < gpu_count => gpu_index = gpu,
+            Ok(_) | Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use config::SystemConfig;
+
+    fn snapshot(gpu: GpuSnapshot) -> Snapshot {
+        Snapshot {
+            gpu: Some(gpu),
+            ..Snapshot::default()
+        }
+    }
+
+    fn line(metric: Metric, gpu: GpuSnapshot) -> Option<String> {
+        metric.line(&snapshot(gpu), &Locale::default(), &UnitFormat::default())
+    }
+
+    #[test]
+    fn sampler_follows_gpu_commands() {
+        let (command_tx, command_rx) = channel();
+        let (snapshot_tx, snapshot_rx) = channel();
+        let system = SystemSource::with_root("/nonexistent", &SystemConfig::default());
+        let source = MockSource { devices: 2 };
+        let interval = Duration::from_secs(60);
+        let sampler = std::thread::spawn(move || {
+            run_sampler(source, system, interval, command_rx, snapshot_tx)
+        });
+        match snapshot_rx.recv().unwrap() {
+            Message::Devices(devices) => assert_eq!(devices, ["Mock GPU 0", "Mock GPU 1"]),
+            _ => panic!("expected the devices first"),
+        }
+        let next = || match snapshot_rx.recv().unwrap() {
+            Message::Metrics(snapshot) => snapshot,
+            _ => panic!("expected a snapshot"),
+        };
+
+        let first = next();
+        assert_eq!(first.gpu_index, 0);
+        assert_eq!(first.gpu.map(|gpu| gpu.temperature), Some(0));
+        assert_eq!(first.processes[0].pid, 100);
+        assert_eq!(first.driver.driver_version, "1.2.3");
+        command_tx.send(Action::CycleGpu).unwrap();
+        assert_eq!(next().gpu_index, 1);
+        command_tx.send(Action::CycleGpu).unwrap();
+        assert_eq!(next().gpu_index, 0);
+        command_tx.send(Action::SelectGpu(1)).unwrap();
+        let selected = next();
+        assert_eq!(selected.gpu.map(|gpu| gpu.temperature), Some(1));
+        // There is no third card.
+        command_tx.send(Action::SelectGpu(2)).unwrap();
+        assert_eq!(next().gpu_index, 1);
+
+        drop(command_tx);
+        sampler.join().unwrap();
+    }
+
+    #[test]
+    fn performance_state() {
+        let gpu = GpuSnapshot {
+            performance_state: Some(2),
+            ..GpuSnapshot::default()
+        };
+        let known = line(Metric::PerformanceState, gpu);
+        assert_eq!(known.as_ref().map(String::as_str), Some("Performance state: P2"));
+        let unknown = line(Metric::PerformanceState, GpuSnapshot::default());
+        assert_eq!(unknown.as_ref().map(String::as_str), Some("Performance state: -"));
+    }
+
+    #[test]
+    fn throttle_reasons_are_translated() {
+        let gpu = GpuSnapshot {
+            throttle_reasons: vec!["power_cap".to_string(), "hw_slowdown".to_string()],
+            ..GpuSnapshot::default()
+        };
+        let dutch = Locale::new(Some("nl"));
+        let units = UnitFormat::default();
+        let line = Metric::ThrottleReasons.line(&snapshot(gpu), &dutch, &units);
+        let expected = "Afgeremd door: vermogenslimiet, hardwarevertraging";
+        assert_eq!(line.as_ref().map(String::as_str), Some(expected));
+        let none = Metric::ThrottleReasons.line(&snapshot(GpuSnapshot::default()), &dutch, &units);
+        assert_eq!(none.as_ref().map(String::as_str), Some("Afgeremd door: niets"));
+    }
+
+    #[test]
+    fn pcie() {
+        let gpu = GpuSnapshot {
+            pcie_generation: 3,
+            pcie_width: 16,
+            pcie_tx: 2048,
+            pcie_rx: 512,
+            ..GpuSnapshot::default()
+        };
+        let text = |metric| line(metric, gpu.clone()).unwrap();
+        assert_eq!(text(Metric::PcieLink), "PCIe link: Gen 3 x16");
+        assert_eq!(text(Metric::PcieTx), "PCIe send: 2.0 KiB/s");
+        assert_eq!(text(Metric::PcieRx), "PCIe receive: 512 B/s");
+    }
+
+    #[test]
+    fn ecc_errors() {
+        let text = |ecc_errors| {
+            let gpu = GpuSnapshot {
+                ecc_errors,
+                ..GpuSnapshot::default()
+            };
+            line(Metric::EccErrors, gpu).unwrap()
+        };
+        assert_eq!(text(None), "ECC errors: -");
+        assert_eq!(text(Some(3)), "ECC errors: 3");
+    }
+
+    #[test]
+    fn lines_need_a_value() {
+        let locale = Locale::default();
+        let units = UnitFormat::default();
+        let empty = Snapshot::default();
+        assert_eq!(Metric::Temperature.line(&empty, &locale, &units), None);
+        assert_eq!(Metric::Battery.line(&empty, &locale, &units), None);
+    }
+}
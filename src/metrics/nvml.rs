@@ -0,0 +1,135 @@
+//! Graphics card metrics from the NVIDIA management library.
+
+use nvml_wrapper::bitmasks::device::ThrottleReasons;
+use nvml_wrapper::enum_wrappers::device::{
+    Clock, EccCounter, MemoryError, PcieUtilCounter, TemperatureSensor,
+};
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use nvml_wrapper::struct_wrappers::device::ProcessInfo;
+use nvml_wrapper::{Device, NVML};
+
+use error::Error;
+
+use super::process::{self, GpuProcess, ProcessTable};
+use super::{DriverInfo, GpuSnapshot, MetricsSource, THROTTLE_REASONS};
+
+pub struct NvmlSource {
+    nvml: NVML,
+    processes: ProcessTable,
+}
+
+impl NvmlSource {
+    pub fn new() -> Result<NvmlSource, Error> {
+        let nvml = NVML::init().map_err(|e| Error::Metrics(e.to_string()))?;
+        Ok(NvmlSource {
+            nvml,
+            processes: ProcessTable::default(),
+        })
+    }
+}
+
+impl MetricsSource for NvmlSource {
+    fn devices(&self) -> Vec<String> {
+        let count = self.nvml.device_count().unwrap_or(0);
+        (0..count)
+            .map(|i| {
+                self.nvml
+                    .device_by_index(i)
+                    .and_then(|d| d.name())
+                    .unwrap_or_else(|_| "unknown".to_string())
+            })
+            .collect()
+    }
+
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        let card = self.nvml.device_by_index(gpu).ok()?;
+        let memory = card.memory_info().ok();
+        let utilization = card.utilization_rates().ok();
+        Some(GpuSnapshot {
+            memory_clock: card.clock_info(Clock::Memory).unwrap_or(0),
+            graphics_clock: card.clock_info(Clock::Graphics).unwrap_or(0),
+            video_clock: card.clock_info(Clock::Video).unwrap_or(0),
+            max_memory_clock: card.max_clock_info(Clock::Memory).unwrap_or(0),
+            max_graphics_clock: card.max_clock_info(Clock::Graphics).unwrap_or(0),
+            temperature: card.temperature(TemperatureSensor::Gpu).unwrap_or(0),
+            fan_speed: card.fan_speed().unwrap_or(0),
+            memory_used: memory.as_ref().map_or(0, |m| m.used),
+            memory_free: memory.as_ref().map_or(0, |m| m.free),
+            memory_total: memory.as_ref().map_or(0, |m| m.total),
+            memory_utilization: utilization.as_ref().map_or(0, |u| u.memory),
+            gpu_utilization: utilization.as_ref().map_or(0, |u| u.gpu),
+            encoder_utilization: card.encoder_utilization().map_or(0, |u| u.utilization),
+            decoder_utilization: card.decoder_utilization().map_or(0, |u| u.utilization),
+            power_usage: card.power_usage().unwrap_or(0),
+            power_limit: card.power_management_limit().unwrap_or(0),
+            performance_state: card
+                .performance_state()
+                .ok()
+                .map(|state| state.as_c() as u32)
+                .filter(|&state| state <= 15),
+            throttle_reasons: throttle_reasons(&card),
+            pcie_generation: card.current_pcie_link_gen().unwrap_or(0),
+            pcie_width: card.current_pcie_link_width().unwrap_or(0),
+            // NVML counts PCIe traffic in KB/s.
+            pcie_tx: card.pcie_throughput(PcieUtilCounter::Send).map_or(0, |kb| kb as u64 * 1024),
+            pcie_rx: card
+                .pcie_throughput(PcieUtilCounter::Receive)
+                .map_or(0, |kb| kb as u64 * 1024),
+            ecc_errors: card
+                .total_ecc_errors(MemoryError::Uncorrected, EccCounter::Volatile)
+                .ok(),
+        })
+    }
+
+    fn processes(&mut self, gpu: u32) -> Vec<GpuProcess> {
+        let card = match self.nvml.device_by_index(gpu) {
+            Ok(card) => card,
+            Err(_) => return Vec::new(),
+        };
+        let compute = card.running_compute_processes().unwrap_or_default();
+        let graphics = card.running_graphics_processes().unwrap_or_default();
+        let table = &self.processes;
+        let describe = |info: ProcessInfo| {
+            let used_memory = match info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => Some(bytes),
+                UsedGpuMemory::Unavailable => None,
+            };
+            table.describe(info.pid, used_memory)
+        };
+        process::merge(compute.into_iter().chain(graphics).map(describe).collect())
+    }
+
+    fn driver(&self) -> DriverInfo {
+        // The CUDA version comes as 1000 * major + 10 * minor.
+        let cuda_version = match self.nvml.sys_cuda_driver_version() {
+            Ok(version) => format!("{}.{}", version / 1000, version % 1000 / 10),
+            Err(_) => String::new(),
+        };
+        DriverInfo {
+            driver_version: self.nvml.sys_driver_version().unwrap_or_default(),
+            cuda_version,
+        }
+    }
+}
+
+fn throttle_reasons(card: &Device) -> Vec<String> {
+    let reasons = match card.current_throttle_reasons() {
+        Ok(reasons) => reasons,
+        Err(_) => return Vec::new(),
+    };
+    let flags = [
+        ThrottleReasons::GPU_IDLE,
+        ThrottleReasons::APPLICATIONS_CLOCKS_SETTING,
+        ThrottleReasons::SW_POWER_CAP,
+        ThrottleReasons::HW_SLOWDOWN,
+        ThrottleReasons::SYNC_BOOST,
+        ThrottleReasons::SW_THERMAL_SLOWDOWN,
+        ThrottleReasons::HW_THERMAL_SLOWDOWN,
+    ];
+    flags
+        .iter()
+        .zip(THROTTLE_REASONS.iter())
+        .filter(|&(&flag, _)| reasons.contains(flag))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
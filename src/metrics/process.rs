@@ -0,0 +1,201 @@
+//! The processes using a graphics card, with their names and owners read from `/proc`.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use units::{Quantity, UnitFormat};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+    /// Graphics memory in bytes, when the driver reports it.
+    pub used_memory: Option<u64>,
+}
+
+impl GpuProcess {
+    /// `pid name user memory`, with the name and user cut to a fixed length.
+    pub fn line(&self, units: &UnitFormat) -> String {
+        let memory = match self.used_memory {
+            Some(bytes) => units.format(Quantity::Bytes(bytes)),
+            None => "-".to_string(),
+        };
+        format!(
+            "{} {} {} {}",
+            self.pid,
+            truncate(&self.name, 16),
+            truncate(&self.user, 10),
+            memory
+        )
+    }
+}
+
+fn truncate(s: &str, length: usize) -> String {
+    if s.chars().count() <= length {
+        s.to_string()
+    } else {
+        let mut s: String = s.chars().take(length - 1).collect();
+        s.push('…');
+        s
+    }
+}
+
+/// Looks up process names and owners in the `proc` and `etc/passwd` below a root directory,
+/// which is `/` unless pointed at a copy.
+#[derive(Debug, Clone)]
+pub struct ProcessTable {
+    root: PathBuf,
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        ProcessTable::new("/")
+    }
+}
+
+impl ProcessTable {
+    pub fn new<P: Into<PathBuf>>(root: P) -> ProcessTable {
+        ProcessTable { root: root.into() }
+    }
+
+    pub fn describe(&self, pid: u32, used_memory: Option<u64>) -> GpuProcess {
+        let dir = self.root.join("proc").join(pid.to_string());
+        let name = read_trimmed(&dir.join("comm")).unwrap_or_else(|| "?".to_string());
+        let user = match self.uid(&dir) {
+            Some(uid) => self.user_name(uid).unwrap_or_else(|| uid.to_string()),
+            None => "?".to_string(),
+        };
+        GpuProcess {
+            pid,
+            name,
+            user,
+            used_memory,
+        }
+    }
+
+    /// The real user id from the `Uid:` line of the process status.
+    fn uid(&self, dir: &Path) -> Option<u32> {
+        let status = std::fs::read_to_string(dir.join("status")).ok()?;
+        status
+            .lines()
+            .find(|line| line.starts_with("Uid:"))?
+            .split_whitespace()
+            .nth(1)?
+            .parse()
+            .ok()
+    }
+
+    fn user_name(&self, uid: u32) -> Option<String> {
+        let passwd = std::fs::read_to_string(self.root.join("etc/passwd")).ok()?;
+        passwd.lines().find_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            match fields.nth(1)?.parse::<u32>() {
+                Ok(id) if id == uid => Some(name.to_string()),
+                _ => None,
+            }
+        })
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// Merge the lists for the same card, keep one entry per process and put the biggest users of
+/// graphics memory first.
+#[cfg(feature = "nvml")]
+pub fn merge(mut processes: Vec<GpuProcess>) -> Vec<GpuProcess> {
+    processes.sort_by(|a, b| b.used_memory.cmp(&a.used_memory));
+    let mut seen = std::collections::HashSet::new();
+    processes.retain(|p| seen.insert(p.pid));
+    processes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use metrics::write_fixture;
+
+    fn table(root: &Path) -> ProcessTable {
+        write_fixture(
+            root,
+            "etc/passwd",
+            "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000:Alice:/home/alice:/bin/sh\n",
+        );
+        write_fixture(root, "proc/42/comm", "firefox\n");
+        write_fixture(root, "proc/42/status", "Name:\tfirefox\nUid:\t1000\t1000\t1000\t1000\n");
+        write_fixture(root, "proc/43/comm", "Xorg\n");
+        write_fixture(root, "proc/43/status", "Name:\tXorg\nUid:\t1234\t0\t0\t0\n");
+        ProcessTable::new(root)
+    }
+
+    #[cfg(feature = "nvml")]
+    fn process(pid: u32, used_memory: Option<u64>) -> GpuProcess {
+        GpuProcess {
+            pid,
+            used_memory,
+            ..GpuProcess::default()
+        }
+    }
+
+    #[test]
+    fn describe() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = table(dir.path());
+        let firefox = table.describe(42, Some(1 << 20));
+        assert_eq!(
+            firefox,
+            GpuProcess {
+                pid: 42,
+                name: "firefox".to_string(),
+                user: "alice".to_string(),
+                used_memory: Some(1 << 20),
+            }
+        );
+        // A uid without a passwd entry is shown as the number.
+        let xorg = table.describe(43, None);
+        assert_eq!((xorg.name.as_str(), xorg.user.as_str()), ("Xorg", "1234"));
+        // The process may have exited since the driver listed it.
+        let gone = table.describe(44, None);
+        assert_eq!((gone.name.as_str(), gone.user.as_str()), ("?", "?"));
+    }
+
+    #[cfg(feature = "nvml")]
+    #[test]
+    fn merge_keeps_one_entry_per_process() {
+        let merged = merge(vec![
+            process(1, Some(100)),
+            process(2, None),
+            process(3, Some(300)),
+            process(1, Some(200)),
+        ]);
+        let pids: Vec<(u32, Option<u64>)> =
+            merged.iter().map(|p| (p.pid, p.used_memory)).collect();
+        assert_eq!(pids, [(3, Some(300)), (1, Some(200)), (2, None)]);
+    }
+
+    #[test]
+    fn line_truncates_name_and_user() {
+        let process = GpuProcess {
+            pid: 7,
+            name: "a-very-long-process-name".to_string(),
+            user: "administrator".to_string(),
+            used_memory: Some(512 << 20),
+        };
+        let units = UnitFormat::default();
+        assert_eq!(process.line(&units), "7 a-very-long-pro… administr… 512.0 MiB");
+        let short = GpuProcess {
+            name: "vlc".to_string(),
+            user: "bob".to_string(),
+            used_memory: None,
+            ..process
+        };
+        assert_eq!(short.line(&units), "7 vlc bob -");
+    }
+}
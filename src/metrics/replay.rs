@@ -0,0 +1,380 @@
+//! Recording the samples of a graphics card and playing them back.
+//!
+//! A recording is a file with one JSON object per line: first a header with the cards and driver
+//! versions, then every sample, process list and set of system figures with the milliseconds
+//! since recording started. `ReplaySource` plays a recording as if it were the hardware, at any
+//! speed and in a loop, so a display problem seen under some load can be looked at again on
+//! another machine.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use error::Error;
+
+use super::{DriverInfo, GpuProcess, GpuSnapshot, MetricsSource, SystemSnapshot, SystemSource};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    Header {
+        devices: Vec<String>,
+        driver: DriverInfo,
+    },
+    Sample {
+        ms: u64,
+        gpu: u32,
+        sample: Option<GpuSnapshot>,
+    },
+    Processes {
+        ms: u64,
+        gpu: u32,
+        processes: Vec<GpuProcess>,
+    },
+    System {
+        ms: u64,
+        system: SystemSnapshot,
+    },
+}
+
+/// A `MetricsSource` that writes everything it reads from `source` to a recording.
+pub struct Recorder<S> {
+    source: S,
+    /// `None` after a write failed, so a full disk is reported once.
+    file: Option<BufWriter<File>>,
+    started: Instant,
+}
+
+impl<S: MetricsSource> Recorder<S> {
+    pub fn new(source: S, file: File) -> Recorder<S> {
+        let mut recorder = Recorder {
+            source,
+            file: Some(BufWriter::new(file)),
+            started: Instant::now(),
+        };
+        let header = Entry::Header {
+            devices: recorder.source.devices(),
+            driver: recorder.source.driver(),
+        };
+        recorder.write(&header);
+        recorder
+    }
+
+    fn ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn write(&mut self, entry: &Entry) {
+        let written = match self.file {
+            Some(ref mut file) => write_entry(file, entry),
+            None => return,
+        };
+        if let Err(e) = written {
+            eprintln!("could not write the recording, stopped recording: {}", e);
+            self.file = None;
+        }
+    }
+}
+
+/// One line of JSON, flushed right away since the sampler thread is not joined at exit.
+fn write_entry<W: Write>(mut writer: W, entry: &Entry) -> std::io::Result<()> {
+    serde_json::to_writer(&mut writer, entry)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+impl<S: MetricsSource> MetricsSource for Recorder<S> {
+    fn devices(&self) -> Vec<String> {
+        self.source.devices()
+    }
+
+    fn tick(&mut self) {
+        self.source.tick()
+    }
+
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        let sample = self.source.sample_gpu(gpu);
+        let entry = Entry::Sample {
+            ms: self.ms(),
+            gpu,
+            sample: sample.clone(),
+        };
+        self.write(&entry);
+        sample
+    }
+
+    fn processes(&mut self, gpu: u32) -> Vec<GpuProcess> {
+        let processes = self.source.processes(gpu);
+        let entry = Entry::Processes {
+            ms: self.ms(),
+            gpu,
+            processes: processes.clone(),
+        };
+        self.write(&entry);
+        processes
+    }
+
+    fn driver(&self) -> DriverInfo {
+        self.source.driver()
+    }
+
+    fn sample_system(&mut self, system: &mut SystemSource) -> SystemSnapshot {
+        let system = self.source.sample_system(system);
+        let entry = Entry::System {
+            ms: self.ms(),
+            system: system.clone(),
+        };
+        self.write(&entry);
+        system
+    }
+}
+
+/// Plays a recording back at `speed` times real time, starting over when it reaches the end.
+#[derive(Debug, Clone)]
+pub struct ReplaySource {
+    devices: Vec<String>,
+    driver: DriverInfo,
+    entries: Vec<Entry>,
+    /// Length of the recording in milliseconds.
+    length: u64,
+    speed: f64,
+    started: Option<Instant>,
+    /// Position in the recording as of the last tick, in milliseconds.
+    position: u64,
+}
+
+impl ReplaySource {
+    pub fn open(path: &Path, speed: f64) -> Result<ReplaySource, Error> {
+        let invalid = |message: String| {
+            Error::Metrics(format!("invalid recording {}: {}", path.display(), message))
+        };
+        let file = File::open(path).map_err(|e| {
+            Error::io(&format!("could not open recording {}", path.display()), e)
+        })?;
+
+        let mut devices = Vec::new();
+        let mut driver = DriverInfo::default();
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| invalid(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = serde_json::from_str(&line)
+                .map_err(|e| invalid(format!("line {}: {}", number + 1, e)))?;
+            match entry {
+                Entry::Header {
+                    devices: recorded_devices,
+                    driver: recorded_driver,
+                } => {
+                    devices = recorded_devices;
+                    driver = recorded_driver;
+                }
+                entry => entries.push(entry),
+            }
+        }
+
+        let length = entries.iter().map(ms).max().unwrap_or(0);
+        Ok(ReplaySource {
+            devices,
+            driver,
+            entries,
+            length,
+            speed: if speed > 0.0 { speed } else { 1.0 },
+            started: None,
+            position: 0,
+        })
+    }
+
+    /// Move to the position of `now`. The clock starts with the first tick.
+    fn tick_at(&mut self, now: Instant) {
+        let started = *self.started.get_or_insert(now);
+        let ms = (now.duration_since(started).as_millis() as f64 * self.speed) as u64;
+        // Wrap around one past the end, so the last entry gets its turn too.
+        self.position = ms % (self.length + 1);
+    }
+
+    /// The last entry `pick` accepts at or before the position, or the first one while the
+    /// position is still before it.
+    fn latest<'a, T, F>(&'a self, pick: F) -> Option<&'a T>
+    where
+        F: Fn(&'a Entry) -> Option<&'a T>,
+    {
+        let mut picked = self.entries.iter().filter_map(|entry| Some((ms(entry), pick(entry)?)));
+        let first = picked.next()?;
+        let position = self.position;
+        let latest = picked.take_while(|&(ms, _)| ms <= position).last();
+        Some(latest.unwrap_or(first).1)
+    }
+}
+
+fn ms(entry: &Entry) -> u64 {
+    match entry {
+        Entry::Header { .. } => 0,
+        Entry::Sample { ms, .. } | Entry::Processes { ms, .. } | Entry::System { ms, .. } => *ms,
+    }
+}
+
+impl MetricsSource for ReplaySource {
+    fn devices(&self) -> Vec<String> {
+        self.devices.clone()
+    }
+
+    fn tick(&mut self) {
+        self.tick_at(Instant::now())
+    }
+
+    /// The last sample of card `gpu` recorded before the current position.
+    fn sample_gpu(&mut self, gpu: u32) -> Option<GpuSnapshot> {
+        self.latest(|entry| match entry {
+            Entry::Sample {
+                gpu: g, sample, ..
+            } if *g == gpu => Some(sample),
+            _ => None,
+        })?
+        .clone()
+    }
+
+    fn processes(&mut self, gpu: u32) -> Vec<GpuProcess> {
+        self.latest(|entry| match entry {
+            Entry::Processes {
+                gpu: g, processes, ..
+            } if *g == gpu => Some(processes),
+            _ => None,
+        })
+        .cloned()
+        .unwrap_or_default()
+    }
+
+    fn driver(&self) -> DriverInfo {
+        self.driver.clone()
+    }
+
+    /// The recorded figures instead of those of this machine; none from older recordings.
+    fn sample_system(&mut self, _system: &mut SystemSource) -> SystemSnapshot {
+        self.latest(|entry| match entry {
+            Entry::System { system, .. } => Some(system),
+            _ => None,
+        })
+        .cloned()
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use config::SystemConfig;
+    use metrics::MockSource;
+
+    fn sample(ms: u64, temperature: u32) -> Entry {
+        Entry::Sample {
+            ms,
+            gpu: 0,
+            sample: Some(GpuSnapshot {
+                temperature,
+                ..GpuSnapshot::default()
+            }),
+        }
+    }
+
+    /// A recording of one card at 1 °C after 100 ms, 2 °C after 200 ms and 3 °C after 300 ms.
+    fn recording(path: &Path) {
+        let mut file = File::create(path).unwrap();
+        let header = Entry::Header {
+            devices: vec!["Recorded GPU".to_string()],
+            driver: DriverInfo::default(),
+        };
+        for entry in &[header, sample(100, 1), sample(200, 2), sample(300, 3)] {
+            write_entry(&mut file, entry).unwrap();
+        }
+    }
+
+    fn temperature(replay: &mut ReplaySource) -> Option<u32> {
+        replay.sample_gpu(0).map(|gpu| gpu.temperature)
+    }
+
+    #[test]
+    fn recording_plays_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        let mut system = SystemSource::with_root("/nonexistent", &SystemConfig::default());
+        let mut source = MockSource { devices: 2 };
+        let recorded = source.sample_system(&mut system);
+        let mut recorder = Recorder::new(source, File::create(&path).unwrap());
+        recorder.tick();
+        recorder.sample_gpu(0);
+        recorder.sample_gpu(1);
+        recorder.processes(1);
+        recorder.sample_system(&mut system);
+        drop(recorder);
+
+        let mut replay = ReplaySource::open(&path, 1.0).unwrap();
+        assert_eq!(replay.devices(), ["Mock GPU 0", "Mock GPU 1"]);
+        assert_eq!(replay.driver().driver_version, "1.2.3");
+        replay.tick();
+        assert_eq!(replay.sample_gpu(1).map(|gpu| gpu.temperature), Some(1));
+        assert_eq!(replay.sample_gpu(0).map(|gpu| gpu.temperature), Some(0));
+        assert_eq!(replay.sample_gpu(2), None);
+        assert_eq!(replay.processes(1)[0].pid, 101);
+        assert!(replay.processes(0).is_empty());
+        assert_eq!(replay.sample_system(&mut system), recorded);
+    }
+
+    #[test]
+    fn position_follows_the_clock_and_wraps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        recording(&path);
+        let mut replay = ReplaySource::open(&path, 1.0).unwrap();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // Before the first entry it shows that entry rather than nothing.
+        replay.tick_at(start);
+        assert_eq!(temperature(&mut replay), Some(1));
+        replay.tick_at(at(250));
+        assert_eq!(temperature(&mut replay), Some(2));
+        replay.tick_at(at(300));
+        assert_eq!(temperature(&mut replay), Some(3));
+        replay.tick_at(at(301));
+        assert_eq!(temperature(&mut replay), Some(1));
+        replay.tick_at(at(301 + 200));
+        assert_eq!(temperature(&mut replay), Some(2));
+    }
+
+    #[test]
+    fn speed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        recording(&path);
+        let mut replay = ReplaySource::open(&path, 2.0).unwrap();
+        let start = Instant::now();
+        replay.tick_at(start);
+        replay.tick_at(start + Duration::from_millis(125));
+        assert_eq!(temperature(&mut replay), Some(2));
+
+        assert_eq!(ReplaySource::open(&path, 0.0).unwrap().speed, 1.0);
+        assert_eq!(ReplaySource::open(&path, -2.0).unwrap().speed, 1.0);
+    }
+
+    #[test]
+    fn corrupt_line_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        recording(&path);
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.insert_str(contents.find('\n').unwrap() + 1, "{\"type\": \"sample\"\n");
+        std::fs::write(&path, contents).unwrap();
+        match ReplaySource::open(&path, 1.0) {
+            Err(Error::Metrics(message)) => assert!(message.contains("line 2"), "{}", message),
+            other => panic!("expected a metrics error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Network, disk, filesystem and battery figures of the machine itself.
+//!
+//! Interface and disk counters come from `/proc/net/dev` and `/proc/diskstats`; rates are the
+//! change between two samples divided by the time between them, so the first sample has none.
+//! Filesystem usage comes from `statvfs` and the battery from `PowerSupplies`. Everything is read
+//! below a root directory, which is `/` unless pointed at a copy.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use super::battery::{BatteryStatus, PowerSupplies};
+use config::SystemConfig;
+use locale::Locale;
+use units::{Quantity, UnitFormat};
+
+/// Bytes per sector in `/proc/diskstats`, whatever the disk's real sector size.
+const SECTOR: u64 = 512;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemSnapshot {
+    pub interfaces: Vec<InterfaceRates>,
+    pub disks: Vec<DiskRates>,
+    pub filesystems: Vec<FilesystemUsage>,
+    /// `None` on machines without a battery.
+    pub battery: Option<BatteryStatus>,
+}
+
+/// Traffic of a network interface, in bytes per second.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceRates {
+    pub name: String,
+    pub rx: u64,
+    pub tx: u64,
+}
+
+impl InterfaceRates {
+    pub fn line(&self, locale: &Locale, units: &UnitFormat) -> String {
+        let rx = units.format(Quantity::BytesPerSecond(self.rx));
+        let tx = units.format(Quantity::BytesPerSecond(self.tx));
+        locale.system(
+            "network",
+            &[("name", self.name.as_str()), ("rx", rx.as_str()), ("tx", tx.as_str())],
+        )
+    }
+}
+
+/// Throughput of a disk, in bytes per second.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskRates {
+    pub name: String,
+    pub read: u64,
+    pub write: u64,
+}
+
+impl DiskRates {
+    pub fn line(&self, locale: &Locale, units: &UnitFormat) -> String {
+        let read = units.format(Quantity::BytesPerSecond(self.read));
+        let write = units.format(Quantity::BytesPerSecond(self.write));
+        locale.system(
+            "disk",
+            &[
+                ("name", self.name.as_str()),
+                ("read", read.as_str()),
+                ("write", write.as_str()),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilesystemUsage {
+    pub mount: String,
+    pub total: u64,
+    /// Bytes not in use, including those reserved for root.
+    pub free: u64,
+    /// Bytes available to normal users.
+    pub available: u64,
+}
+
+impl FilesystemUsage {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// Like `df`, the share of the space normal users can have that is in use.
+    pub fn percent(&self) -> f64 {
+        match self.used() + self.available {
+            0 => 0.0,
+            usable => self.used() as f64 * 100.0 / usable as f64,
+        }
+    }
+
+    pub fn line(&self, locale: &Locale, units: &UnitFormat) -> String {
+        let used = units.format(Quantity::Bytes(self.used()));
+        let total = units.format(Quantity::Bytes(self.total));
+        let percent = units.format(Quantity::Percent(self.percent()));
+        locale.system(
+            "filesystem",
+            &[
+                ("mount", self.mount.as_str()),
+                ("used", used.as_str()),
+                ("total", total.as_str()),
+                ("percent", percent.as_str()),
+            ],
+        )
+    }
+}
+
+/// Counters of the previous sample, per device.
+#[derive(Debug, Clone, Default)]
+struct Counters {
+    at: Option<Instant>,
+    interfaces: HashMap<String, (u64, u64)>,
+    disks: HashMap<String, (u64, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemSource {
+    root: PathBuf,
+    interfaces: Vec<String>,
+    disks: Vec<String>,
+    filesystems: Vec<String>,
+    batteries: PowerSupplies,
+    previous: Counters,
+}
+
+impl SystemSource {
+    pub fn new(config: &SystemConfig) -> SystemSource {
+        SystemSource::with_root("/", config)
+    }
+
+    pub fn with_root<P: Into<PathBuf>>(root: P, config: &SystemConfig) -> SystemSource {
+        let root = root.into();
+        SystemSource {
+            batteries: PowerSupplies::new(root.clone()),
+            root,
+            interfaces: config.interfaces.clone(),
+            disks: config.disks.clone(),
+            filesystems: config.filesystems.clone(),
+            previous: Counters::default(),
+        }
+    }
+
+    pub fn sample(&mut self) -> SystemSnapshot {
+        self.sample_at(Instant::now())
+    }
+
+    /// Sample with `now` as the current time, which the rates are computed from.
+    pub fn sample_at(&mut self, now: Instant) -> SystemSnapshot {
+        let interfaces: HashMap<_, _> = read(&self.root.join("proc/net/dev"))
+            .map(|text| parse_net_dev(&text))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&(ref name, _)| selected(&self.interfaces, name, || name != "lo"))
+            .collect();
+        let block = self.root.join("sys/block");
+        let disks: HashMap<_, _> = read(&self.root.join("proc/diskstats"))
+            .map(|text| parse_diskstats(&text))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&(ref name, _)| {
+                // By default only whole disks, which are the ones listed in /sys/block.
+                selected(&self.disks, name, || {
+                    let virtual_disk = name.starts_with("loop") || name.starts_with("ram");
+                    !virtual_disk && block.join(name).exists()
+                })
+            })
+            .collect();
+
+        let elapsed = self
+            .previous
+            .at
+            .map(|at| (now - at).as_secs_f64())
+            .filter(|&secs| secs > 0.0);
+        let previous = &self.previous;
+        let rate = |old: Option<&(u64, u64)>, new: (u64, u64)| match (old, elapsed) {
+            (Some(old), Some(secs)) => (
+                (new.0.saturating_sub(old.0) as f64 / secs) as u64,
+                (new.1.saturating_sub(old.1) as f64 / secs) as u64,
+            ),
+            _ => (0, 0),
+        };
+        let mut snapshot = SystemSnapshot {
+            interfaces: interfaces
+                .iter()
+                .map(|(name, &counters)| {
+                    let (rx, tx) = rate(previous.interfaces.get(name), counters);
+                    InterfaceRates {
+                        name: name.clone(),
+                        rx,
+                        tx,
+                    }
+                })
+                .collect(),
+            disks: disks
+                .iter()
+                .map(|(name, &counters)| {
+                    let (read, write) = rate(previous.disks.get(name), counters);
+                    DiskRates {
+                        name: name.clone(),
+                        read,
+                        write,
+                    }
+                })
+                .collect(),
+            filesystems: self.filesystem_usage(),
+            battery: self.batteries.sample(),
+        };
+        snapshot.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        snapshot.disks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.previous = Counters {
+            at: Some(now),
+            interfaces,
+            disks,
+        };
+        snapshot
+    }
+
+    fn filesystem_usage(&self) -> Vec<FilesystemUsage> {
+        let default = ["/".to_string()];
+        let mounts = if self.filesystems.is_empty() {
+            &default[..]
+        } else {
+            &self.filesystems[..]
+        };
+        mounts
+            .iter()
+            .filter_map(|mount| {
+                // Mount points are relative to the root, like the /proc files.
+                let path = self.root.join(mount.trim_start_matches('/'));
+                let (total, free, available) = statvfs(&path)?;
+                Some(FilesystemUsage {
+                    mount: mount.clone(),
+                    total,
+                    free,
+                    available,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Whether device `name` is shown: the configured ones, or those passing `default` when none are.
+fn selected<F: FnOnce() -> bool>(configured: &[String], name: &str, default: F) -> bool {
+    if configured.is_empty() {
+        default()
+    } else {
+        configured.iter().any(|c| c == name)
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+/// `(interface, (received bytes, sent bytes))` for every line of `/proc/net/dev`.
+pub fn parse_net_dev(text: &str) -> Vec<(String, (u64, u64))> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            let name = parts.next()?.trim();
+            let fields: Vec<u64> = parts
+                .next()?
+                .split_whitespace()
+                .filter_map(|f| f.parse().ok())
+                .collect();
+            // Eight receive columns come before the sent bytes.
+            match (fields.first(), fields.get(8)) {
+                (Some(&rx), Some(&tx)) => Some((name.to_string(), (rx, tx))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// `(disk, (bytes read, bytes written))` for every line of `/proc/diskstats`.
+pub fn parse_diskstats(text: &str) -> Vec<(String, (u64, u64))> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // major minor name reads merged sectors_read ms writes merged sectors_written ...
+            let name = fields.get(2)?;
+            let read: u64 = fields.get(5)?.parse().ok()?;
+            let written: u64 = fields.get(9)?.parse().ok()?;
+            Some((name.to_string(), (read * SECTOR, written * SECTOR)))
+        })
+        .collect()
+}
+
+/// `(total, free, available)` bytes of the filesystem holding `path`.
+#[cfg(unix)]
+fn statvfs(path: &Path) -> Option<(u64, u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((
+        stat.f_blocks as u64 * block,
+        stat.f_bfree as u64 * block,
+        stat.f_bavail as u64 * block,
+    ))
+}
+
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> Option<(u64, u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use metrics::write_fixture;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                            |  Transmit
+ face |bytes packets errs drop fifo frame compressed multicast|bytes packets errs drop fifo ...
+    lo: 1000 10 0 0 0 0 0 0 1000 10 0 0 0 0 0 0
+  eth0: 5000000 4000 0 0 0 0 0 0 2000000 3000 0 0 0 0 0 0
+";
+
+    const DISKSTATS: &str = "\
+   7       0 loop0 10 0 20 0 0 0 0 0 0 0 0
+   8       0 sda 1000 0 2048 100 500 0 4096 50 0 0 0
+   8       1 sda1 900 0 1024 90 400 0 2048 40 0 0 0
+";
+
+    #[test]
+    fn net_dev() {
+        let interfaces = parse_net_dev(NET_DEV);
+        let expected = [
+            ("lo".to_string(), (1000, 1000)),
+            ("eth0".to_string(), (5_000_000, 2_000_000)),
+        ];
+        assert_eq!(interfaces, expected);
+    }
+
+    #[test]
+    fn diskstats() {
+        let disks = parse_diskstats(DISKSTATS);
+        assert_eq!(disks[1], ("sda".to_string(), (2048 * SECTOR, 4096 * SECTOR)));
+        assert_eq!(disks.len(), 3);
+    }
+
+    #[test]
+    fn rates_between_two_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_fixture(root, "proc/net/dev", NET_DEV);
+        write_fixture(root, "proc/diskstats", DISKSTATS);
+        std::fs::create_dir_all(root.join("sys/block/sda")).unwrap();
+        std::fs::create_dir_all(root.join("sys/block/loop0")).unwrap();
+
+        let mut source = SystemSource::with_root(root, &SystemConfig::default());
+        let t0 = Instant::now();
+        let first = source.sample_at(t0);
+        let idle = [InterfaceRates {
+            name: "eth0".to_string(),
+            rx: 0,
+            tx: 0,
+        }];
+        assert_eq!(first.interfaces, idle);
+        assert_eq!(first.disks.len(), 1);
+        assert_eq!(first.disks[0].read, 0);
+        assert_eq!(first.battery, None);
+
+        write_fixture(
+            root,
+            "proc/net/dev",
+            &NET_DEV.replace("5000000 4000", "6000000 5000").replace("2000000", "2500000"),
+        );
+        write_fixture(
+            root,
+            "proc/diskstats",
+            &DISKSTATS.replace("sda 1000 0 2048", "sda 1100 0 4096"),
+        );
+        let second = source.sample_at(t0 + Duration::from_secs(2));
+        let busy = [InterfaceRates {
+            name: "eth0".to_string(),
+            rx: 500_000,
+            tx: 250_000,
+        }];
+        assert_eq!(second.interfaces, busy);
+        let sda = DiskRates {
+            name: "sda".to_string(),
+            read: 1024 * SECTOR,
+            write: 0,
+        };
+        assert_eq!(second.disks, [sda]);
+        // The root filesystem is the temporary directory's.
+        assert_eq!(second.filesystems.len(), 1);
+        assert_eq!(second.filesystems[0].mount, "/");
+    }
+
+    #[test]
+    fn configured_devices() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_fixture(root, "proc/net/dev", NET_DEV);
+        write_fixture(root, "proc/diskstats", DISKSTATS);
+        let config = SystemConfig {
+            interfaces: vec!["lo".to_string()],
+            disks: vec!["sda1".to_string()],
+            filesystems: vec!["/missing".to_string()],
+        };
+        let snapshot = SystemSource::with_root(root, &config).sample();
+        let interfaces: Vec<&str> = snapshot.interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(interfaces, ["lo"]);
+        let disks: Vec<&str> = snapshot.disks.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(disks, ["sda1"]);
+        assert!(snapshot.filesystems.is_empty());
+    }
+
+    #[test]
+    fn filesystem_usage_leaves_out_reserved_space() {
+        const GIB: u64 = 1 << 30;
+        let usage = FilesystemUsage {
+            mount: "/home".to_string(),
+            total: 100 * GIB,
+            free: 40 * GIB,
+            available: 35 * GIB,
+        };
+        assert_eq!(usage.used(), 60 * GIB);
+        let line = usage.line(&Locale::default(), &UnitFormat::default());
+        assert_eq!(line, "/home: 60.0 GiB of 100.0 GiB used (63%)");
+    }
+}
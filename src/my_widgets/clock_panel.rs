@@ -0,0 +1,264 @@
+//! The clock with the hardware panel below it, as a widget that can be placed in any parent.
+//!
+//! `ClockPanel` draws what the clock window shows, minus the window level overlays (help and the
+//! context menu), inside its own rectangle, arranged by the `App`'s current layout. The colours
+//! follow the `App`'s theme and night schedule unless they are set on the widget.
+
+use conrod_core::widget::{self, Widget};
+use conrod_core::{Color, Colorable, FontSize, Positionable, Range, Rect, Sizeable, UiCell};
+
+use fonts::FontChain;
+use gui::App;
+use layout::{Align, Cell, Panel};
+use my_widgets::TabularText;
+use theme::Palette;
+
+#[derive(WidgetCommon)]
+pub struct ClockPanel<'a> {
+    #[conrod(common_builder)]
+    common: widget::CommonBuilder,
+    app: &'a App,
+    style: Style,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, WidgetStyle)]
+pub struct Style {
+    /// Background colour, instead of the theme's.
+    #[conrod(default = "None")]
+    pub color: Option<Option<Color>>,
+    /// Text colour, instead of the theme's.
+    #[conrod(default = "None")]
+    pub text_color: Option<Option<Color>>,
+    #[conrod(default = "120")]
+    pub clock_font_size: Option<FontSize>,
+    #[conrod(default = "24")]
+    pub font_size: Option<FontSize>,
+}
+
+widget_ids! {
+    struct Ids {
+        background,
+        cells[],
+        statistics[],
+    }
+}
+
+pub struct State {
+    ids: Ids,
+}
+
+impl<'a> ClockPanel<'a> {
+    pub fn new(app: &'a App) -> Self {
+        ClockPanel {
+            common: widget::CommonBuilder::default(),
+            app,
+            style: Style::default(),
+        }
+    }
+
+    pub fn text_color(mut self, color: Color) -> Self {
+        self.style.text_color = Some(Some(color));
+        self
+    }
+
+    pub fn clock_font_size(mut self, size: FontSize) -> Self {
+        self.style.clock_font_size = Some(size);
+        self
+    }
+
+    pub fn font_size(mut self, size: FontSize) -> Self {
+        self.style.font_size = Some(size);
+        self
+    }
+}
+
+impl<'a> Widget for ClockPanel<'a> {
+    type State = State;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        State {
+            ids: Ids::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            style,
+            rect,
+            ui,
+            ..
+        } = args;
+        let app = self.app;
+
+        let theme_palette = app.palette();
+        let palette = Palette {
+            background: style.color(&ui.theme).unwrap_or(theme_palette.background),
+            text: style.text_color(&ui.theme).unwrap_or(theme_palette.text),
+        };
+
+        widget::Rectangle::fill(rect.dim())
+            .color(palette.background)
+            .middle_of(id)
+            .parent(id)
+            .graphics_for(id)
+            .set(state.ids.background, ui);
+
+        let cells = match app.layouts.get(app.layout) {
+            Some(layout) => layout.cells(rect),
+            None => return,
+        };
+        if state.ids.cells.len() < cells.len() {
+            let id_gen = &mut ui.widget_id_generator();
+            state.update(|state| {
+                state.ids.cells.resize(cells.len(), id_gen);
+                state.ids.statistics.resize(cells.len(), id_gen);
+            });
+        }
+
+        let hide_at_night = app.night.hide_hardware && app.night_level() >= 0.5;
+        let show_metrics = app.show_hardware && !hide_at_night;
+        let clock_size = style.clock_font_size(&ui.theme);
+        let font_size = style.font_size(&ui.theme);
+        let time = app.time_text();
+        let date = app.date_text();
+        let ids = state.ids.cells.iter().zip(state.ids.statistics.iter());
+        for (cell, (&cell_id, &statistics_id)) in cells.iter().zip(ids) {
+            let scaled = |size: FontSize| app.font_size((size as f64 * cell.scale) as FontSize);
+            let line;
+            let (s, fonts, size) = match cell.panel {
+                Panel::Empty => continue,
+                Panel::Clock => (&time, &app.fonts.clock, scaled(clock_size)),
+                Panel::Date => (&date, &app.fonts.metrics, scaled(font_size)),
+                Panel::Metric(metric) if show_metrics => {
+                    match metric.line(&app.metrics, &app.locale, &app.units) {
+                        Some(text) => line = text,
+                        None => continue,
+                    }
+                    if let Some(statistics) = app.statistics(metric) {
+                        // The statistics go below the metric in smaller text.
+                        let size = scaled(font_size);
+                        let (top, bottom) = split(cell, 0.4);
+                        let label = text(&app.fonts.metrics, &line, size, palette.text);
+                        place(label, ui, &top, id, cell_id);
+                        let small = (size as f64 * 0.6) as FontSize;
+                        let label = text(&app.fonts.metrics, &statistics, small, palette.text);
+                        place(label, ui, &bottom, id, statistics_id);
+                        continue;
+                    }
+                    (&line, &app.fonts.metrics, scaled(font_size))
+                }
+                Panel::Metric(_) => continue,
+                Panel::Processes | Panel::Network | Panel::Disks | Panel::Filesystems
+                    if show_metrics =>
+                {
+                    let size = scaled(font_size);
+                    list(ui, app, cell, size, palette.text, id, cell_id);
+                    continue;
+                }
+                Panel::Processes | Panel::Network | Panel::Disks | Panel::Filesystems => continue,
+            };
+            let label = text(fonts, s, size, palette.text);
+            place(label, ui, cell, id, cell_id);
+        }
+    }
+}
+
+impl<'a> Colorable for ClockPanel<'a> {
+    fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(Some(color));
+        self
+    }
+}
+
+/// Tabular text drawing each character in the first font of `fonts` that has it, so digits keep a
+/// fixed width.
+fn text<'a>(fonts: &'a FontChain, s: &'a str, size: FontSize, color: Color) -> TabularText<'a> {
+    TabularText::new(s)
+        .font_chain(fonts)
+        .font_size(size)
+        .color(color)
+}
+
+/// As many lines of a list panel as fit in `cell`, starting at the top.
+fn list(
+    ui: &mut UiCell,
+    app: &App,
+    cell: &Cell,
+    size: FontSize,
+    color: Color,
+    panel: widget::Id,
+    id: widget::Id,
+) {
+    let line_height = size as f64 * 1.2;
+    let fit = (cell.rect.h() / line_height).floor() as usize;
+    let (metrics, locale, units) = (&app.metrics, &app.locale, &app.units);
+    let lines: Vec<String> = match cell.panel {
+        Panel::Processes => metrics.processes.iter().map(|p| p.line(units)).collect(),
+        Panel::Network => metrics
+            .system
+            .interfaces
+            .iter()
+            .map(|i| i.line(locale, units))
+            .collect(),
+        Panel::Disks => metrics.system.disks.iter().map(|d| d.line(locale, units)).collect(),
+        Panel::Filesystems => metrics
+            .system
+            .filesystems
+            .iter()
+            .map(|f| f.line(locale, units))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let list = lines.into_iter().take(fit).collect::<Vec<_>>().join("\n");
+    let list = widget::Text::new(&list)
+        .font_id(app.fonts.metrics.pick(&ui.fonts, &list))
+        .font_size(size)
+        .color(color)
+        .wh(cell.rect.dim())
+        .xy(cell.rect.xy())
+        .parent(panel)
+        .graphics_for(panel);
+    let list = match cell.align {
+        Align::Left => list.left_justify(),
+        Align::Center => list.center_justify(),
+        Align::Right => list.right_justify(),
+    };
+    list.set(id, ui);
+}
+
+/// `cell` cut in two, with the bottom part `share` of its height.
+fn split(cell: &Cell, share: f64) -> (Cell, Cell) {
+    let rect = cell.rect;
+    let cut = rect.bottom() + rect.h() * share;
+    let top = Rect {
+        x: rect.x,
+        y: Range::new(cut, rect.top()),
+    };
+    let bottom = Rect {
+        x: rect.x,
+        y: Range::new(rect.bottom(), cut),
+    };
+    (Cell { rect: top, ..*cell }, Cell { rect: bottom, ..*cell })
+}
+
+/// Set `text` in the middle of `cell`, against its left or right edge when aligned that way.
+fn place(text: TabularText, ui: &mut UiCell, cell: &Cell, panel: widget::Id, id: widget::Id) {
+    let half_width = text.width(ui) / 2.0;
+    let x = match cell.align {
+        Align::Left => cell.rect.left() + half_width,
+        Align::Center => cell.rect.x(),
+        Align::Right => cell.rect.right() - half_width,
+    };
+    text.x_y(x, cell.rect.y())
+        .parent(panel)
+        .graphics_for(panel)
+        .set(id, ui);
+}
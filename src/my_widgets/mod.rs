@@ -0,0 +1,7 @@
+//! Custom conrod widgets used by the clock.
+
+mod clock_panel;
+mod tabular_text;
+
+pub use self::clock_panel::ClockPanel;
+pub use self::tabular_text::TabularText;
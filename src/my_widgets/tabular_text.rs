@@ -0,0 +1,165 @@
+//! A single line of text where every digit gets a cell of the same width.
+//!
+//! With a proportional font a centred `widget::Text` moves whenever the digit widths change, which
+//! makes a ticking clock jitter. `TabularText` lays out each character itself: digits are centred
+//! in cells as wide as the widest digit and all other characters use their own advance width, so
+//! the total width only depends on the shape of the string. Given a `FontChain`, every character
+//! is drawn in the first font of the chain that has it.
+
+use conrod_core::text::{self, font};
+use conrod_core::widget::{self, Widget};
+use conrod_core::{Color, Colorable, FontSize, Positionable, Scalar, Ui};
+
+use fonts::FontChain;
+
+#[derive(WidgetCommon)]
+pub struct TabularText<'a> {
+    #[conrod(common_builder)]
+    common: widget::CommonBuilder,
+    text: &'a str,
+    /// Fonts to pick from per character, instead of the style's single font.
+    fonts: Option<&'a FontChain>,
+    style: Style,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, WidgetStyle)]
+pub struct Style {
+    #[conrod(default = "theme.label_color")]
+    pub color: Option<Color>,
+    #[conrod(default = "theme.font_size_medium")]
+    pub font_size: Option<FontSize>,
+    #[conrod(default = "theme.font_id")]
+    pub font_id: Option<Option<font::Id>>,
+}
+
+widget_ids! {
+    struct Ids {
+        chars[],
+    }
+}
+
+pub struct State {
+    ids: Ids,
+}
+
+impl<'a> TabularText<'a> {
+    pub fn new(text: &'a str) -> Self {
+        TabularText {
+            common: widget::CommonBuilder::default(),
+            text,
+            fonts: None,
+            style: Style::default(),
+        }
+    }
+
+    pub fn font_size(mut self, size: FontSize) -> Self {
+        self.style.font_size = Some(size);
+        self
+    }
+
+    pub fn font_id(mut self, font_id: font::Id) -> Self {
+        self.style.font_id = Some(Some(font_id));
+        self
+    }
+
+    pub fn font_chain(mut self, fonts: &'a FontChain) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    /// The font `c` is drawn with.
+    fn font_for(&self, ui: &Ui, c: char) -> Option<font::Id> {
+        match self.fonts {
+            Some(chain) => Some(chain.pick_char(&ui.fonts, c)),
+            None => self.style.font_id(&ui.theme).or_else(|| ui.fonts.ids().next()),
+        }
+    }
+
+    /// The width the text takes up.
+    pub fn width(&self, ui: &Ui) -> Scalar {
+        self.cells(ui).iter().sum()
+    }
+
+    /// The width of every character cell, in order.
+    fn cells(&self, ui: &Ui) -> Vec<Scalar> {
+        let scale = text::pt_to_scale(self.style.font_size(&ui.theme));
+        let advance = |c: char| match self.font_for(ui, c).and_then(|id| ui.fonts.get(id)) {
+            Some(font) => font.glyph(c).scaled(scale).h_metrics().advance_width as Scalar,
+            None => 0.0,
+        };
+        let digit = "0123456789".chars().map(&advance).fold(0.0, Scalar::max);
+        self.text
+            .chars()
+            .map(|c| if c.is_ascii_digit() { digit } else { advance(c) })
+            .collect()
+    }
+}
+
+impl<'a> Widget for TabularText<'a> {
+    type State = State;
+    type Style = Style;
+    type Event = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        State {
+            ids: Ids::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {
+        self.style
+    }
+
+    fn default_x_dimension(&self, ui: &Ui) -> conrod_core::position::Dimension {
+        conrod_core::position::Dimension::Absolute(self.width(ui))
+    }
+
+    fn default_y_dimension(&self, ui: &Ui) -> conrod_core::position::Dimension {
+        conrod_core::position::Dimension::Absolute(self.style.font_size(&ui.theme) as Scalar)
+    }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            style,
+            rect,
+            ui,
+            ..
+        } = args;
+
+        let cells = self.cells(ui);
+        if state.ids.chars.len() < cells.len() {
+            let id_gen = &mut ui.widget_id_generator();
+            state.update(|state| state.ids.chars.resize(cells.len(), id_gen));
+        }
+
+        let color = style.color(&ui.theme);
+        let font_size = style.font_size(&ui.theme);
+
+        // Place each character at the centre of its cell, starting from the left edge.
+        let mut x = rect.left() - rect.x();
+        let mut buf = [0u8; 4];
+        for ((c, width), &char_id) in self.text.chars().zip(cells).zip(state.ids.chars.iter()) {
+            let s: &str = c.encode_utf8(&mut buf);
+            let mut glyph = widget::Text::new(s)
+                .font_size(font_size)
+                .color(color)
+                .x_y_relative_to(id, x + width / 2.0, 0.0)
+                .parent(id)
+                .graphics_for(id);
+            if let Some(font_id) = self.font_for(ui, c) {
+                glyph = glyph.font_id(font_id);
+            }
+            glyph.set(char_id, ui);
+            x += width;
+        }
+    }
+}
+
+impl<'a> Colorable for TabularText<'a> {
+    fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+}
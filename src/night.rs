@@ -0,0 +1,336 @@
+//! Day/night schedule used to dim the clock at night.
+//!
+//! The night either runs between two fixed times of day or from sunset to sunrise at a configured
+//! location. `NightSchedule::level` turns the current time into a value between `0.0` (day) and
+//! `1.0` (full night), fading linearly over the configured transition.
+
+use chrono::{Datelike, NaiveDate, NaiveTime, TimeZone, Timelike};
+
+use config::NightConfig;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Official zenith for sunrise and sunset, including refraction and the solar disc.
+const ZENITH: f64 = 90.833;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Schedule {
+    Fixed { start: NaiveTime, end: NaiveTime },
+    Sun { latitude: f64, longitude: f64 },
+}
+
+/// Result of the sunrise calculation for one day.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SunTimes {
+    /// Sunrise and sunset in UTC.
+    Normal {
+        sunrise: NaiveTime,
+        sunset: NaiveTime,
+    },
+    /// The sun does not set (polar day).
+    AlwaysUp,
+    /// The sun does not rise (polar night).
+    AlwaysDown,
+}
+
+#[derive(Debug, Clone)]
+pub struct NightSchedule {
+    pub enabled: bool,
+    pub schedule: Schedule,
+    pub transition: chrono::Duration,
+    pub brightness: f32,
+    pub hide_hardware: bool,
+}
+
+impl NightSchedule {
+    pub fn from_config(config: &NightConfig) -> NightSchedule {
+        let schedule = match (config.latitude, config.longitude) {
+            (Some(latitude), Some(longitude)) => Schedule::Sun {
+                latitude,
+                longitude,
+            },
+            _ => Schedule::Fixed {
+                start: parse_time("start", &config.start, (22, 30)),
+                end: parse_time("end", &config.end, (7, 0)),
+            },
+        };
+        NightSchedule {
+            enabled: config.enabled,
+            schedule,
+            transition: chrono::Duration::minutes(config.transition_minutes as i64),
+            brightness: config.brightness.max(0.0).min(1.0),
+            hide_hardware: config.hide_hardware,
+        }
+    }
+
+    /// How far into the night `now` is, from `0.0` for day to `1.0` for full night.
+    pub fn level<Tz: TimeZone>(&self, now: &chrono::DateTime<Tz>) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        let (start, end) = match self.schedule {
+            Schedule::Fixed { start, end } => (start, end),
+            Schedule::Sun {
+                latitude,
+                longitude,
+            } => match sun_times(now.naive_utc().date(), latitude, longitude) {
+                SunTimes::Normal { sunrise, sunset } => {
+                    // The schedule works in local time, so shift the UTC times by our offset.
+                    let offset = now.naive_local() - now.naive_utc();
+                    (sunset + offset, sunrise + offset)
+                }
+                SunTimes::AlwaysUp => return 0.0,
+                SunTimes::AlwaysDown => return 1.0,
+            },
+        };
+        night_level(now.naive_local().time(), start, end, self.transition)
+    }
+}
+
+/// The `HH:MM` time of setting `name`, or `default` after a warning when it is not one.
+fn parse_time(name: &str, s: &str, default: (u32, u32)) -> NaiveTime {
+    match NaiveTime::parse_from_str(s.trim(), "%H:%M") {
+        Ok(time) => time,
+        Err(_) => {
+            eprintln!("invalid night {} time, expected HH:MM: {}", name, s);
+            NaiveTime::from_hms_opt(default.0, default.1, 0).unwrap()
+        }
+    }
+}
+
+/// Seconds from `from` to `to`, going forward and wrapping around midnight.
+fn seconds_until(from: NaiveTime, to: NaiveTime) -> f64 {
+    let diff = to.num_seconds_from_midnight() as f64 - from.num_seconds_from_midnight() as f64;
+    if diff < 0.0 {
+        diff + SECONDS_PER_DAY
+    } else {
+        diff
+    }
+}
+
+/// Night level at time of day `t` for a night running from `start` to `end`.
+///
+/// Dimming begins at `start` and brightening at `end`, each taking `transition`.
+pub fn night_level(
+    t: NaiveTime,
+    start: NaiveTime,
+    end: NaiveTime,
+    transition: chrono::Duration,
+) -> f32 {
+    let transition = transition.num_seconds() as f64;
+    let night_length = seconds_until(start, end);
+    let since_start = seconds_until(start, t);
+    let ramp = |since: f64| {
+        if transition <= 0.0 {
+            1.0
+        } else {
+            (since / transition).min(1.0)
+        }
+    };
+    if since_start < night_length {
+        ramp(since_start) as f32
+    } else {
+        (1.0 - ramp(seconds_until(end, t))) as f32
+    }
+}
+
+/// Sunrise and sunset (UTC) on `date` at the given location.
+///
+/// Uses the sunrise equation from the Almanac for Computers, which is accurate to about a minute
+/// away from the polar circles.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> SunTimes {
+    match (
+        sun_event(date, latitude, longitude, true),
+        sun_event(date, latitude, longitude, false),
+    ) {
+        (Ok(sunrise), Ok(sunset)) => SunTimes::Normal { sunrise, sunset },
+        (Err(always_up), _) | (_, Err(always_up)) => {
+            if always_up {
+                SunTimes::AlwaysUp
+            } else {
+                SunTimes::AlwaysDown
+            }
+        }
+    }
+}
+
+/// Time of sunrise or sunset, or `Err(true)` when the sun stays up and `Err(false)` when it stays
+/// down all day.
+fn sun_event(date: NaiveDate, latitude: f64, longitude: f64, rising: bool) -> Result<NaiveTime, bool> {
+    let day = date.ordinal() as f64;
+    let lng_hour = longitude / 15.0;
+    let t = if rising {
+        day + (6.0 - lng_hour) / 24.0
+    } else {
+        day + (18.0 - lng_hour) / 24.0
+    };
+
+    let mean_anomaly = 0.9856 * t - 3.289;
+    let true_longitude = normalize(
+        mean_anomaly
+            + 1.916 * sin(mean_anomaly)
+            + 0.020 * sin(2.0 * mean_anomaly)
+            + 282.634,
+        360.0,
+    );
+
+    let mut right_ascension = normalize(atan(0.91764 * tan(true_longitude)), 360.0);
+    // Put the right ascension in the same quadrant as the true longitude.
+    right_ascension += (true_longitude / 90.0).floor() * 90.0 - (right_ascension / 90.0).floor() * 90.0;
+    right_ascension /= 15.0;
+
+    let sin_dec = 0.39782 * sin(true_longitude);
+    let cos_dec = sin_dec.asin().cos();
+    let cos_hour = (cos(ZENITH) - sin_dec * sin(latitude)) / (cos_dec * cos(latitude));
+    if cos_hour > 1.0 {
+        return Err(false);
+    }
+    if cos_hour < -1.0 {
+        return Err(true);
+    }
+
+    let hour_angle = if rising {
+        360.0 - acos(cos_hour)
+    } else {
+        acos(cos_hour)
+    } / 15.0;
+    let local_mean_time = hour_angle + right_ascension - 0.06571 * t - 6.622;
+    let utc = normalize(local_mean_time - lng_hour, 24.0);
+
+    let seconds = (utc * 3600.0).round() as u32 % 86400;
+    Ok(NaiveTime::from_num_seconds_from_midnight_opt(seconds, 0).unwrap())
+}
+
+fn normalize(value: f64, max: f64) -> f64 {
+    let value = value % max;
+    if value < 0.0 {
+        value + max
+    } else {
+        value
+    }
+}
+
+fn sin(deg: f64) -> f64 {
+    deg.to_radians().sin()
+}
+
+fn cos(deg: f64) -> f64 {
+    deg.to_radians().cos()
+}
+
+fn tan(deg: f64) -> f64 {
+    deg.to_radians().tan()
+}
+
+fn atan(x: f64) -> f64 {
+    x.atan().to_degrees()
+}
+
+fn acos(x: f64) -> f64 {
+    x.acos().to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Whether `actual` is within two minutes of `expected`, both as times of day.
+    fn close(actual: NaiveTime, expected: NaiveTime) -> bool {
+        let diff = seconds_until(expected, actual).min(seconds_until(actual, expected));
+        diff <= 120.0
+    }
+
+    fn assert_sun(times: SunTimes, sunrise: NaiveTime, sunset: NaiveTime) {
+        match times {
+            SunTimes::Normal {
+                sunrise: rise,
+                sunset: set,
+            } => {
+                assert!(close(rise, sunrise), "sunrise {} instead of {}", rise, sunrise);
+                assert!(close(set, sunset), "sunset {} instead of {}", set, sunset);
+            }
+            other => panic!("expected sunrise and sunset, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn amsterdam_midsummer() {
+        let times = sun_times(date(2026, 6, 21), 52.37, 4.89);
+        assert_sun(times, time(3, 18), time(20, 7));
+    }
+
+    #[test]
+    fn new_york_midsummer() {
+        // Sunset is after midnight UTC.
+        let times = sun_times(date(2026, 6, 21), 40.71, -74.01);
+        assert_sun(times, time(9, 25), time(0, 31));
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        assert_eq!(sun_times(date(2026, 6, 21), 78.0, 15.0), SunTimes::AlwaysUp);
+        assert_eq!(sun_times(date(2026, 12, 21), 78.0, 15.0), SunTimes::AlwaysDown);
+    }
+
+    #[test]
+    fn level_at_start_and_end() {
+        let half_hour = chrono::Duration::minutes(30);
+        let level = |t| night_level(t, time(22, 0), time(6, 0), half_hour);
+        assert_eq!(level(time(22, 0)), 0.0);
+        assert_eq!(level(time(22, 30)), 1.0);
+        assert_eq!(level(time(6, 0)), 1.0);
+        assert_eq!(level(time(6, 30)), 0.0);
+        assert_eq!(level(time(12, 0)), 0.0);
+    }
+
+    #[test]
+    fn level_mid_transition() {
+        let half_hour = chrono::Duration::minutes(30);
+        let level = |t| night_level(t, time(22, 0), time(6, 0), half_hour);
+        assert_eq!(level(time(22, 15)), 0.5);
+        assert_eq!(level(time(6, 15)), 0.5);
+    }
+
+    #[test]
+    fn level_across_midnight() {
+        let half_hour = chrono::Duration::minutes(30);
+        let level = |t| night_level(t, time(22, 0), time(6, 0), half_hour);
+        assert_eq!(level(time(23, 59)), 1.0);
+        assert_eq!(level(time(0, 0)), 1.0);
+        assert_eq!(level(time(3, 0)), 1.0);
+    }
+
+    #[test]
+    fn level_without_transition() {
+        let level = |t| night_level(t, time(22, 0), time(6, 0), chrono::Duration::zero());
+        assert_eq!(level(time(21, 59)), 0.0);
+        assert_eq!(level(time(22, 0)), 1.0);
+        assert_eq!(level(time(5, 59)), 1.0);
+        assert_eq!(level(time(6, 0)), 0.0);
+    }
+
+    #[test]
+    fn invalid_times_fall_back() {
+        let config = NightConfig {
+            start: "late".to_string(),
+            end: "25:00".to_string(),
+            ..NightConfig::default()
+        };
+        let schedule = NightSchedule::from_config(&config);
+        assert_eq!(
+            schedule.schedule,
+            Schedule::Fixed {
+                start: time(22, 30),
+                end: time(7, 0),
+            }
+        );
+    }
+}
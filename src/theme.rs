@@ -0,0 +1,60 @@
+//! Colours used to draw the clock, and blending between the day and night variants.
+
+use conrod_core::color::{self, Color};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Palette {
+    pub background: Color,
+    pub text: Color,
+}
+
+/// The day themes the user can cycle through; the first one is the default.
+pub const THEMES: [Palette; 3] = [
+    Palette {
+        background: color::DARK_CHARCOAL,
+        text: color::WHITE,
+    },
+    Palette {
+        background: color::BLACK,
+        text: color::WHITE,
+    },
+    Palette {
+        background: color::WHITE,
+        text: color::BLACK,
+    },
+];
+
+impl Palette {
+    /// The dim theme, with the text brightness scaled down to `brightness`.
+    pub fn night(brightness: f32) -> Palette {
+        Palette {
+            background: color::BLACK,
+            text: scale(color::rgb(1.0, 0.55, 0.4), brightness),
+        }
+    }
+
+    /// Linearly blend from `self` to `other`, `t` going from `0.0` to `1.0`.
+    pub fn blend(&self, other: &Palette, t: f32) -> Palette {
+        Palette {
+            background: mix(self.background, other.background, t),
+            text: mix(self.text, other.text, t),
+        }
+    }
+}
+
+fn scale(c: Color, factor: f32) -> Color {
+    let color::Rgba(r, g, b, a) = c.to_rgb();
+    color::rgba(r * factor, g * factor, b * factor, a)
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    let t = t.max(0.0).min(1.0);
+    let color::Rgba(r1, g1, b1, a1) = from.to_rgb();
+    let color::Rgba(r2, g2, b2, a2) = to.to_rgb();
+    color::rgba(
+        r1 + (r2 - r1) * t,
+        g1 + (g2 - g1) * t,
+        b1 + (b2 - b1) * t,
+        a1 + (a2 - a1) * t,
+    )
+}
@@ -0,0 +1,256 @@
+//! Formatting measured values with their units.
+//!
+//! Values are scaled to the largest unit that keeps them at or above one, like `7.8 GiB` or
+//! `1.4 GHz`. Memory uses binary (KiB, MiB, GiB) or SI (kB, MB, GB) units, and temperatures are
+//! shown in Celsius or Fahrenheit, as configured in the `[units]` section.
+
+use config::UnitsConfig;
+
+/// A measured value in its base unit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Quantity {
+    Bytes(u64),
+    BytesPerSecond(u64),
+    Hertz(f64),
+    Watts(f64),
+    Celsius(f64),
+    Percent(f64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ByteUnits {
+    /// Powers of 1024: KiB, MiB, GiB.
+    Binary,
+    /// Powers of 1000: kB, MB, GB.
+    Si,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UnitFormat {
+    pub bytes: ByteUnits,
+    pub temperature: TemperatureUnit,
+    /// Decimals shown for scaled values; percentages and temperatures are whole numbers.
+    pub precision: usize,
+    pub decimal_separator: char,
+}
+
+impl Default for UnitFormat {
+    fn default() -> Self {
+        UnitFormat {
+            bytes: ByteUnits::Binary,
+            temperature: TemperatureUnit::Celsius,
+            precision: 1,
+            decimal_separator: '.',
+        }
+    }
+}
+
+const BINARY: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+const SI: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+const HERTZ: [&str; 4] = ["Hz", "kHz", "MHz", "GHz"];
+const WATTS: [&str; 4] = ["mW", "W", "kW", "MW"];
+
+impl Quantity {
+    /// The number in the quantity's base unit.
+    pub fn value(self) -> f64 {
+        match self {
+            Quantity::Bytes(bytes) | Quantity::BytesPerSecond(bytes) => bytes as f64,
+            Quantity::Hertz(value)
+            | Quantity::Watts(value)
+            | Quantity::Celsius(value)
+            | Quantity::Percent(value) => value,
+        }
+    }
+
+    /// The same kind of quantity with another value, like an average of several.
+    pub fn with_value(self, value: f64) -> Quantity {
+        match self {
+            Quantity::Bytes(_) => Quantity::Bytes(value.round() as u64),
+            Quantity::BytesPerSecond(_) => Quantity::BytesPerSecond(value.round() as u64),
+            Quantity::Hertz(_) => Quantity::Hertz(value),
+            Quantity::Watts(_) => Quantity::Watts(value),
+            Quantity::Celsius(_) => Quantity::Celsius(value),
+            Quantity::Percent(_) => Quantity::Percent(value),
+        }
+    }
+}
+
+impl UnitFormat {
+    pub fn from_config(config: &UnitsConfig) -> UnitFormat {
+        let mut format = UnitFormat {
+            precision: config.precision,
+            ..UnitFormat::default()
+        };
+        match config.bytes.as_str() {
+            "binary" => format.bytes = ByteUnits::Binary,
+            "si" => format.bytes = ByteUnits::Si,
+            other => eprintln!("unknown byte units: {}", other),
+        }
+        match config.temperature.as_str() {
+            "celsius" => format.temperature = TemperatureUnit::Celsius,
+            "fahrenheit" => format.temperature = TemperatureUnit::Fahrenheit,
+            other => eprintln!("unknown temperature unit: {}", other),
+        }
+        format
+    }
+
+    pub fn format(&self, quantity: Quantity) -> String {
+        match quantity {
+            Quantity::Bytes(bytes) => match self.bytes {
+                ByteUnits::Binary => self.scaled(bytes as f64, 1024.0, &BINARY),
+                ByteUnits::Si => self.scaled(bytes as f64, 1000.0, &SI),
+            },
+            Quantity::BytesPerSecond(rate) => {
+                format!("{}/s", self.format(Quantity::Bytes(rate)))
+            }
+            Quantity::Hertz(hz) => self.scaled(hz, 1000.0, &HERTZ),
+            Quantity::Watts(watts) => self.scaled(watts * 1000.0, 1000.0, &WATTS),
+            Quantity::Celsius(celsius) => match self.temperature {
+                TemperatureUnit::Celsius => format!("{:.0} °C", celsius),
+                TemperatureUnit::Fahrenheit => format!("{:.0} °F", celsius * 9.0 / 5.0 + 32.0),
+            },
+            Quantity::Percent(percent) => format!("{:.0}%", percent),
+        }
+    }
+
+    /// `value` divided by `step` until it fits the largest of `units`, which start at the unit
+    /// `value` is in. Values in the smallest unit are whole numbers.
+    fn scaled(&self, value: f64, step: f64, units: &[&str]) -> String {
+        let mut value = value;
+        let mut unit = 0;
+        while value.abs() >= step && unit + 1 < units.len() {
+            value /= step;
+            unit += 1;
+        }
+        let precision = if unit == 0 { 0 } else { self.precision };
+        let number = format!("{:.*}", precision, value);
+        format!("{} {}", number.replace('.', &self.decimal_separator.to_string()), units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn si() -> UnitFormat {
+        UnitFormat {
+            bytes: ByteUnits::Si,
+            ..UnitFormat::default()
+        }
+    }
+
+    #[test]
+    fn binary_bytes() {
+        let units = UnitFormat::default();
+        let bytes = |n| units.format(Quantity::Bytes(n));
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(1023), "1023 B");
+        assert_eq!(bytes(1024), "1.0 KiB");
+        assert_eq!(bytes(1536), "1.5 KiB");
+        assert_eq!(bytes(3 << 20), "3.0 MiB");
+        assert_eq!(bytes(8_375_186_227), "7.8 GiB");
+        assert_eq!(bytes(2 << 40), "2.0 TiB");
+        assert_eq!(bytes(5 << 50), "5.0 PiB");
+        assert_eq!(bytes(1 << 60), "1024.0 PiB");
+    }
+
+    #[test]
+    fn si_bytes() {
+        let units = si();
+        let bytes = |n| units.format(Quantity::Bytes(n));
+        assert_eq!(bytes(999), "999 B");
+        assert_eq!(bytes(1000), "1.0 kB");
+        assert_eq!(bytes(1_500_000), "1.5 MB");
+        assert_eq!(bytes(8_000_000_000), "8.0 GB");
+        assert_eq!(bytes(2_000_000_000_000), "2.0 TB");
+        assert_eq!(bytes(3_000_000_000_000_000), "3.0 PB");
+    }
+
+    #[test]
+    fn bytes_per_second() {
+        assert_eq!(UnitFormat::default().format(Quantity::BytesPerSecond(512)), "512 B/s");
+        assert_eq!(UnitFormat::default().format(Quantity::BytesPerSecond(2048)), "2.0 KiB/s");
+        assert_eq!(si().format(Quantity::BytesPerSecond(12_300_000)), "12.3 MB/s");
+    }
+
+    #[test]
+    fn hertz() {
+        let units = UnitFormat::default();
+        let mhz = |n: f64| units.format(Quantity::Hertz(n * 1_000_000.0));
+        assert_eq!(mhz(300.0), "300.0 MHz");
+        assert_eq!(mhz(1410.0), "1.4 GHz");
+        assert_eq!(mhz(0.5), "500.0 kHz");
+        assert_eq!(units.format(Quantity::Hertz(60.0)), "60 Hz");
+    }
+
+    #[test]
+    fn watts() {
+        let units = UnitFormat::default();
+        let watts = |n| units.format(Quantity::Watts(n));
+        assert_eq!(watts(0.5), "500 mW");
+        assert_eq!(watts(0.0), "0 mW");
+        assert_eq!(watts(1.0), "1.0 W");
+        assert_eq!(watts(150.0), "150.0 W");
+        assert_eq!(watts(1500.0), "1.5 kW");
+    }
+
+    #[test]
+    fn temperatures() {
+        let celsius = UnitFormat::default();
+        assert_eq!(celsius.format(Quantity::Celsius(63.4)), "63 °C");
+        let fahrenheit = UnitFormat {
+            temperature: TemperatureUnit::Fahrenheit,
+            ..UnitFormat::default()
+        };
+        assert_eq!(fahrenheit.format(Quantity::Celsius(100.0)), "212 °F");
+        assert_eq!(fahrenheit.format(Quantity::Celsius(-40.0)), "-40 °F");
+    }
+
+    #[test]
+    fn percent() {
+        let units = UnitFormat::default();
+        assert_eq!(units.format(Quantity::Percent(42.6)), "43%");
+        assert_eq!(units.format(Quantity::Percent(100.0)), "100%");
+    }
+
+    #[test]
+    fn precision_and_separator() {
+        let units = UnitFormat {
+            precision: 2,
+            decimal_separator: ',',
+            ..UnitFormat::default()
+        };
+        assert_eq!(units.format(Quantity::Bytes(1536)), "1,50 KiB");
+        assert_eq!(units.format(Quantity::Watts(0.25)), "250 mW");
+        let whole = UnitFormat {
+            precision: 0,
+            ..UnitFormat::default()
+        };
+        assert_eq!(whole.format(Quantity::Bytes(1792)), "2 KiB");
+    }
+
+    #[test]
+    fn from_config() {
+        let config = UnitsConfig {
+            bytes: "si".to_string(),
+            temperature: "fahrenheit".to_string(),
+            precision: 3,
+        };
+        let units = UnitFormat::from_config(&config);
+        assert_eq!(units.bytes, ByteUnits::Si);
+        assert_eq!(units.temperature, TemperatureUnit::Fahrenheit);
+        assert_eq!(units.precision, 3);
+
+        let unknown = UnitsConfig {
+            bytes: "octal".to_string(),
+            ..UnitsConfig::default()
+        };
+        assert_eq!(UnitFormat::from_config(&unknown).bytes, ByteUnits::Binary);
+    }
+}
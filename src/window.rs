@@ -0,0 +1,209 @@
+//! Moving a borderless window by dragging it, and remembering where the window was.
+//!
+//! The window size, position, monitor and fullscreen state are saved to `clock-window.toml`, next
+//! to the config file, when the clock exits and restored on the next start.
+
+use std::path::{Path, PathBuf};
+
+use glium::glutin::dpi::{LogicalPosition, LogicalSize};
+use glium::glutin::{
+    ElementState, Event, EventsLoop, MouseButton, Window, WindowBuilder, WindowEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use config::{Config, WindowConfig};
+use {WIN_H, WIN_W};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    pub width: f64,
+    pub height: f64,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub monitor: Option<String>,
+    pub fullscreen: bool,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        WindowState {
+            width: WIN_W as f64,
+            height: WIN_H as f64,
+            x: None,
+            y: None,
+            monitor: None,
+            fullscreen: false,
+        }
+    }
+}
+
+impl WindowState {
+    pub fn path() -> PathBuf {
+        Config::path().with_file_name("clock-window.toml")
+    }
+
+    /// The saved state, or the defaults when nothing was saved yet.
+    pub fn load() -> WindowState {
+        WindowState::load_from(&WindowState::path())
+    }
+
+    /// The state saved in `path`, or the defaults when it is missing or unreadable.
+    pub fn load_from(path: &Path) -> WindowState {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        self.save_to(&WindowState::path())
+    }
+
+    pub fn save_to(&self, path: &Path) {
+        let result = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(path, text).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("could not save window state to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Apply the saved size, monitor and fullscreen state and the configured window style.
+    pub fn builder(
+        &self,
+        builder: WindowBuilder,
+        config: &WindowConfig,
+        events_loop: &EventsLoop,
+    ) -> WindowBuilder {
+        let builder = builder
+            .with_dimensions(LogicalSize::new(self.width, self.height))
+            .with_decorations(!config.borderless)
+            .with_always_on_top(config.always_on_top);
+        if !self.fullscreen {
+            return builder;
+        }
+        let monitor = events_loop
+            .get_available_monitors()
+            .find(|m| m.get_name() == self.monitor)
+            .unwrap_or_else(|| events_loop.get_primary_monitor());
+        builder.with_fullscreen(Some(monitor))
+    }
+
+    /// Move the window to the saved position; this can only be done once it exists.
+    pub fn restore_position(&self, window: &Window) {
+        if let (Some(x), Some(y)) = (self.x, self.y) {
+            window.set_position(LogicalPosition::new(x, y));
+        }
+    }
+
+    /// Take over the current state of `window`.
+    ///
+    /// While fullscreen the size and position are left alone, so leaving fullscreen after a
+    /// restart returns to the old window.
+    pub fn update(&mut self, window: &Window) {
+        self.monitor = window.get_current_monitor().get_name();
+        self.fullscreen = window.get_fullscreen().is_some();
+        if self.fullscreen {
+            return;
+        }
+        if let Some(size) = window.get_inner_size() {
+            self.width = size.width;
+            self.height = size.height;
+        }
+        if let Some(position) = window.get_position() {
+            self.x = Some(position.x);
+            self.y = Some(position.y);
+        }
+    }
+}
+
+/// Moves the window while the left button is held down on it.
+#[derive(Debug, Default)]
+pub struct Dragger {
+    cursor: Option<LogicalPosition>,
+    grab: Option<LogicalPosition>,
+}
+
+impl Dragger {
+    pub fn update(&mut self, event: &Event, window: &Window) {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some(*position);
+                let grab = match self.grab {
+                    Some(grab) => grab,
+                    None => return,
+                };
+                if window.get_fullscreen().is_some() {
+                    return;
+                }
+                // The cursor position is relative to the window, so moving the window by the
+                // offset from where it was grabbed keeps the grab point under the cursor.
+                if let Some(origin) = window.get_position() {
+                    window.set_position(LogicalPosition::new(
+                        origin.x + position.x - grab.x,
+                        origin.y + position.y - grab.y,
+                    ));
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.grab = match state {
+                    ElementState::Pressed => self.cursor,
+                    ElementState::Released => None,
+                };
+            }
+            WindowEvent::Focused(false) => self.grab = None,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_state_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clock-window.toml");
+        let state = WindowState {
+            width: 1024.0,
+            height: 600.5,
+            x: Some(-1920.0),
+            y: Some(40.0),
+            monitor: Some("DP-1".to_string()),
+            fullscreen: true,
+        };
+        state.save_to(&path);
+        assert_eq!(WindowState::load_from(&path), state);
+    }
+
+    #[test]
+    fn missing_or_corrupt_file_gives_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clock-window.toml");
+        assert_eq!(WindowState::load_from(&path), WindowState::default());
+        std::fs::write(&path, "width = \"wide\"\n[[[").unwrap();
+        assert_eq!(WindowState::load_from(&path), WindowState::default());
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clock-window.toml");
+        std::fs::write(&path, "fullscreen = true\n").unwrap();
+        let state = WindowState {
+            fullscreen: true,
+            ..WindowState::default()
+        };
+        assert_eq!(WindowState::load_from(&path), state);
+    }
+}